@@ -0,0 +1,66 @@
+use ansi_to_tui::IntoText;
+use ratatui::text::Text;
+
+/// Converts text containing ANSI SGR escape sequences into styled ratatui text.
+/// Other escape sequences are dropped, and text that cannot be parsed is shown
+/// as it is.
+pub fn to_text(content: &str) -> Text<'static> {
+    content
+        .into_text()
+        .unwrap_or_else(|_| Text::raw(content.to_string()))
+}
+
+/// Removes every escape sequence, leaving only the printable text.
+pub fn strip(content: &str) -> String {
+    to_text(content)
+        .lines
+        .iter()
+        .map(|line| {
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color, Modifier, Style};
+
+    fn styles(content: &str) -> Vec<(String, Style)> {
+        to_text(content)
+            .lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .map(|span| (span.content.to_string(), span.style))
+            .collect()
+    }
+
+    #[test]
+    fn reset_clears_the_style() {
+        let spans = styles("\x1b[1;31mred\x1b[0mplain");
+        assert_eq!(spans[0].0, "red");
+        assert_eq!(spans[0].1.fg, Some(Color::Red));
+        assert!(spans[0].1.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(spans[1].0, "plain");
+        assert_ne!(spans[1].1.fg, Some(Color::Red));
+        assert!(!spans[1].1.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn extended_colors_are_read() {
+        let spans = styles("\x1b[38;5;208mindexed\x1b[48;2;1;2;3mrgb");
+        assert_eq!(spans[0].1.fg, Some(Color::Indexed(208)));
+        assert_eq!(spans[1].1.fg, Some(Color::Indexed(208)));
+        assert_eq!(spans[1].1.bg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn strip_keeps_only_the_text() {
+        assert_eq!(strip("\x1b[4mone\x1b[0m\ntwo"), "one\ntwo");
+        assert_eq!(strip("plain"), "plain");
+    }
+}
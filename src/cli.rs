@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+pub static USAGE: &str = "Usage:
+  dicrs                                  Start the dictionary
+  dicrs <file>                           Start with only the given dictionary file
+  dicrs <dictionary> [<word>]            Start on a dictionary, looking the word up
+  dicrs --list                           List the dictionaries
+  dicrs export-anki <file> [--tags]      Export Leitner cards as Anki TSV
+  dicrs import-anki <file>               Add the cards of an Anki TSV export to Leitner
+  dicrs diff <a> <b> [--output <dir>]    Compare the headwords of two dictionaries
+  dicrs validate <dictionary>            Check a dictionary for common problems
+  dicrs index <dictionary>               Build the full-text index of a dictionary
+  dicrs --help                           Show this message";
+
+pub enum Command {
+    Tui,
+    /// Start the interface on a single dictionary file.
+    Open {
+        path: PathBuf,
+    },
+    /// Start the interface on the named dictionary, looking `word` up.
+    Lookup {
+        dictionary: String,
+        word: Option<String>,
+    },
+    List,
+    Help,
+    Diff {
+        a: String,
+        b: String,
+        output: Option<PathBuf>,
+    },
+    Validate {
+        dictionary: String,
+    },
+    /// Build the full-text index of a dictionary.
+    Index {
+        dictionary: String,
+    },
+    #[cfg(feature = "leitner")]
+    ExportAnki {
+        path: PathBuf,
+        tags: bool,
+    },
+    #[cfg(feature = "leitner")]
+    ImportAnki {
+        path: PathBuf,
+    },
+}
+
+/// Parses the command line, `args` excluding the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let args: Vec<String> = args.into_iter().collect();
+    let Some(first) = args.first() else {
+        return Ok(Command::Tui);
+    };
+    let rest = &args[1..];
+    match first.as_str() {
+        "-h" | "--help" => Ok(Command::Help),
+        "-l" | "--list" => Ok(Command::List),
+        "diff" => {
+            let mut output = None;
+            let mut dictionaries = Vec::new();
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "-o" | "--output" => match rest.next() {
+                        Some(dir) => output = Some(PathBuf::from(dir)),
+                        None => return Err("--output expects a directory".to_string()),
+                    },
+                    _ => dictionaries.push(arg.clone()),
+                }
+            }
+            match <[String; 2]>::try_from(dictionaries) {
+                Ok([a, b]) => Ok(Command::Diff { a, b, output }),
+                Err(_) => Err("diff expects exactly two dictionaries".to_string()),
+            }
+        }
+        #[cfg(feature = "leitner")]
+        "export-anki" => {
+            let tags = rest.iter().any(|a| a == "--tags");
+            let mut paths = rest.iter().filter(|a| *a != "--tags");
+            match (paths.next(), paths.next()) {
+                (Some(path), None) => Ok(Command::ExportAnki {
+                    path: PathBuf::from(path),
+                    tags,
+                }),
+                _ => Err("export-anki expects exactly one output file".to_string()),
+            }
+        }
+        #[cfg(feature = "leitner")]
+        "import-anki" => match rest {
+            [path] => Ok(Command::ImportAnki {
+                path: PathBuf::from(path),
+            }),
+            _ => Err("import-anki expects exactly one input file".to_string()),
+        },
+        "validate" => match rest {
+            [dictionary] => Ok(Command::Validate {
+                dictionary: dictionary.clone(),
+            }),
+            _ => Err("validate expects exactly one dictionary".to_string()),
+        },
+        "index" => match rest {
+            [dictionary] => Ok(Command::Index {
+                dictionary: dictionary.clone(),
+            }),
+            _ => Err("index expects exactly one dictionary".to_string()),
+        },
+        other if rest.is_empty() && is_path(other) => Ok(Command::Open {
+            path: PathBuf::from(other),
+        }),
+        other if !other.starts_with('-') => Ok(Command::Lookup {
+            dictionary: other.to_string(),
+            word: (!rest.is_empty()).then(|| rest.join(" ")),
+        }),
+        other => Err(format!("unknown option '{}'", other)),
+    }
+}
+
+/// Whether a lone argument names a file rather than a command: it has a
+/// directory or an extension.
+fn is_path(arg: &str) -> bool {
+    let path = Path::new(arg);
+    path.extension().is_some() || path.components().count() > 1
+}
@@ -0,0 +1,738 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ratatui::style::{Color, Modifier, Style};
+use rust_stemmers::Algorithm;
+
+use crate::favorites::SheetFormat;
+use crate::keymap::{Action, Context, Key};
+use crate::schema::Newlines;
+use crate::search;
+
+/// A value in the configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+/// Parsed configuration file: section name -> key -> value.
+/// Top level keys live in the "" section.
+pub type Document = BTreeMap<String, BTreeMap<String, Value>>;
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Default)]
+pub struct DictionaryConfig {
+    pub ansi: bool,
+    /// Open the dictionary read-write so its entries can be edited.
+    pub writable: bool,
+    /// Column holding the words, detected when not set.
+    pub word_column: Option<String>,
+    /// Column holding the definitions, detected when not set.
+    pub definition_column: Option<String>,
+    /// Color of the dictionary's name, and of the pane titles while it is open.
+    pub color: Option<Color>,
+    /// How line breaks are stored in the definitions, detected when not set.
+    pub newlines: Option<Newlines>,
+    /// Select the first match in index order instead of an exact match
+    /// first, then one starting with the input.
+    pub index_order: bool,
+    /// Stemmer of the dictionary's language, for finding the headword of an
+    /// inflected form that is not one itself.
+    pub stemmer: Option<Algorithm>,
+}
+
+#[cfg(feature = "leitner")]
+pub struct LeitnerConfig {
+    /// Due dates up to this many days away are shown as "In N days".
+    pub relative_days: i64,
+    /// Due dates up to this many weeks away are shown as "In N weeks",
+    /// later ones as "In N months".
+    pub relative_weeks: i64,
+    /// Tag cards exported to Anki from within the app with their box and due date.
+    pub anki_schedule_tags: bool,
+    /// Collect review writes in a transaction committed this often, in
+    /// seconds, instead of writing each review right away.
+    pub save_interval: Option<u64>,
+    /// Most new cards to schedule for a single day; later ones are moved to
+    /// the following days.
+    pub new_cards_per_day: Option<usize>,
+    /// Box new cards start in, from 1 to the number of boxes.
+    pub initial_box: u8,
+    /// Days until a new card is first due; 0 makes it due today.
+    pub initial_delay: i64,
+    /// Days until a card moved into each box is due again, one per box.
+    pub intervals: Vec<u32>,
+}
+
+#[cfg(feature = "leitner")]
+impl Default for LeitnerConfig {
+    fn default() -> Self {
+        Self {
+            relative_days: 10,
+            relative_weeks: 8,
+            anki_schedule_tags: false,
+            save_interval: None,
+            new_cards_per_day: None,
+            initial_box: 1,
+            initial_delay: 1,
+            intervals: crate::leitner::DEFAULT_INTERVALS.to_vec(),
+        }
+    }
+}
+
+pub struct Theme {
+    /// Style of the selected row in the index lists.
+    pub highlight: Style,
+    /// Drop all colors and rely on bold, reverse and underline for emphasis.
+    pub monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            highlight: Style::default().fg(Color::Black).bg(Color::White),
+            monochrome: false,
+        }
+    }
+}
+
+impl Theme {
+    /// Turns the theme monochrome, as requested by the `NO_COLOR` convention.
+    pub fn make_monochrome(&mut self) {
+        self.monochrome = true;
+        if self.highlight.add_modifier.is_empty() {
+            self.highlight = self.highlight.add_modifier(Modifier::REVERSED);
+        }
+    }
+}
+
+pub struct Ui {
+    /// Show a bar next to the index marking the position within the dictionary.
+    pub index_gauge: bool,
+    /// Show a scrollbar on the definition pane when the text is longer than the pane.
+    pub scrollbar: bool,
+    /// Cap the width of definition lines and center the definition pane.
+    pub max_width: Option<u16>,
+    /// Capture the mouse to look up or copy words clicked in a definition.
+    pub mouse: bool,
+    /// Line put between the definitions of entries sharing a headword; an
+    /// empty string leaves a blank line.
+    pub sense_separator: String,
+    /// Number the definitions of entries sharing a headword.
+    pub number_senses: bool,
+    /// Backspace with nothing left to delete closes the overlay or turns the
+    /// filter off.
+    pub backspace_escapes: bool,
+    /// Start in compact mode when the terminal is small.
+    pub auto_compact: bool,
+    /// Fold definitions longer than this many lines until expanded.
+    pub fold_lines: Option<usize>,
+    /// Keep showing the previous definition when a search finds nothing.
+    pub keep_definition: bool,
+    /// Look the input up as it is typed instead of only on Enter.
+    pub live_search: bool,
+    /// How long typing has to pause before the index and the live search
+    /// follow the input.
+    pub live_search_delay: Duration,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self {
+            index_gauge: true,
+            scrollbar: true,
+            max_width: None,
+            mouse: false,
+            sense_separator: String::new(),
+            number_senses: false,
+            backspace_escapes: false,
+            auto_compact: true,
+            fold_lines: None,
+            keep_definition: false,
+            live_search: true,
+            live_search_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+pub struct Config {
+    /// Text-to-speech command, with `%w` standing for the word to pronounce.
+    pub speech_command: Option<String>,
+    pub dictionaries: HashMap<String, DictionaryConfig>,
+    /// Dictionaries listed first, in this order; the rest follow alphabetically.
+    pub dictionary_order: Vec<String>,
+    /// Folders scanned for dictionaries in addition to the default one.
+    pub directories: Vec<PathBuf>,
+    /// File extensions, with the leading dot, recognized as dictionaries.
+    pub extensions: Vec<String>,
+    /// Also copy entries exported as HTML to the clipboard.
+    #[cfg(feature = "clipboard")]
+    pub html_to_clipboard: bool,
+    /// Format of the study sheet of favorites.
+    pub sheet_format: SheetFormat,
+    /// Put each headword of the study sheet on its own heading line.
+    pub sheet_headings: bool,
+    /// Key sequences replacing the default keys of an action, from `[keys.<context>]`.
+    pub key_bindings: Vec<(Context, Action, Vec<Vec<Key>>)>,
+    pub theme: Theme,
+    pub ui: Ui,
+    #[cfg(feature = "leitner")]
+    pub leitner: LeitnerConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            speech_command: None,
+            dictionaries: HashMap::new(),
+            dictionary_order: Vec::new(),
+            directories: Vec::new(),
+            extensions: vec![".db".to_string()],
+            #[cfg(feature = "clipboard")]
+            html_to_clipboard: false,
+            sheet_format: SheetFormat::default(),
+            sheet_headings: true,
+            key_bindings: Vec::new(),
+            theme: Theme::default(),
+            ui: Ui::default(),
+            #[cfg(feature = "leitner")]
+            leitner: LeitnerConfig::default(),
+        }
+    }
+}
+
+static DEFAULT_DICTIONARY: DictionaryConfig = DictionaryConfig {
+    ansi: false,
+    writable: false,
+    word_column: None,
+    definition_column: None,
+    color: None,
+    newlines: None,
+    index_order: false,
+    stemmer: None,
+};
+
+impl Config {
+    /// Loads the configuration from `path`, falling back to the defaults
+    /// when the file does not exist.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::from_document(&parse(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError {
+                line: 0,
+                message: format!("could not read '{}': {}", path.display(), e),
+            }),
+        }
+    }
+
+    pub fn from_document(doc: &Document) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (section, table) in doc {
+            if section == "theme" {
+                config.theme = parse_theme(section, table)?;
+            }
+            if section == "dictionaries" {
+                for (key, value) in table {
+                    match key.as_str() {
+                        "order" => config.dictionary_order = as_string_list(section, key, value)?,
+                        "directories" => {
+                            config.directories = as_string_list(section, key, value)?
+                                .iter()
+                                .map(|dir| match (dir.strip_prefix("~/"), dirs::home_dir()) {
+                                    (Some(rest), Some(home)) => home.join(rest),
+                                    _ => PathBuf::from(dir),
+                                })
+                                .collect();
+                        }
+                        "extensions" => {
+                            config.extensions = as_string_list(section, key, value)?
+                                .into_iter()
+                                .map(|ext| match ext.starts_with('.') {
+                                    true => ext,
+                                    false => format!(".{}", ext),
+                                })
+                                .collect();
+                            if config.extensions.is_empty() {
+                                return Err(type_error(section, key, "at least one extension"));
+                            }
+                        }
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+            }
+            if section == "export" {
+                for (key, value) in table {
+                    match key.as_str() {
+                        #[cfg(feature = "clipboard")]
+                        "html_to_clipboard" => {
+                            config.html_to_clipboard = as_bool(section, key, value)?
+                        }
+                        "sheet_format" => {
+                            config.sheet_format =
+                                SheetFormat::from_name(&as_string(section, key, value)?)
+                                    .ok_or_else(|| {
+                                        type_error(section, key, "\"markdown\" or \"text\"")
+                                    })?
+                        }
+                        "sheet_headings" => config.sheet_headings = as_bool(section, key, value)?,
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+            }
+            if section == "speech" {
+                for (key, value) in table {
+                    match key.as_str() {
+                        "command" => config.speech_command = Some(as_string(section, key, value)?),
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+            }
+            if section == "ui" {
+                for (key, value) in table {
+                    match key.as_str() {
+                        "index_gauge" => config.ui.index_gauge = as_bool(section, key, value)?,
+                        "scrollbar" => config.ui.scrollbar = as_bool(section, key, value)?,
+                        "mouse" => config.ui.mouse = as_bool(section, key, value)?,
+                        "sense_separator" => {
+                            config.ui.sense_separator = as_string(section, key, value)?
+                        }
+                        "number_senses" => config.ui.number_senses = as_bool(section, key, value)?,
+                        "fold_lines" => {
+                            config.ui.fold_lines = Some(as_positive(section, key, value)? as usize)
+                        }
+                        "auto_compact" => config.ui.auto_compact = as_bool(section, key, value)?,
+                        "live_search" => config.ui.live_search = as_bool(section, key, value)?,
+                        "live_search_delay" => {
+                            config.ui.live_search_delay = match as_integer(section, key, value)? {
+                                ms if ms >= 0 => Duration::from_millis(ms as u64),
+                                _ => {
+                                    return Err(type_error(section, key, "a non-negative integer"))
+                                }
+                            }
+                        }
+                        "keep_definition" => {
+                            config.ui.keep_definition = as_bool(section, key, value)?
+                        }
+                        "backspace_escapes" => {
+                            config.ui.backspace_escapes = as_bool(section, key, value)?
+                        }
+                        "max_width" => {
+                            let width = as_positive(section, key, value)?;
+                            config.ui.max_width = Some(width.min(u16::MAX as i64) as u16);
+                        }
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+            }
+            #[cfg(feature = "leitner")]
+            if section == "leitner" {
+                for (key, value) in table {
+                    match key.as_str() {
+                        "relative_days" => {
+                            config.leitner.relative_days = as_positive(section, key, value)?
+                        }
+                        "relative_weeks" => {
+                            config.leitner.relative_weeks = as_positive(section, key, value)?
+                        }
+                        "anki_schedule_tags" => {
+                            config.leitner.anki_schedule_tags = as_bool(section, key, value)?
+                        }
+                        "save_interval" => {
+                            config.leitner.save_interval =
+                                Some(as_positive(section, key, value)? as u64)
+                        }
+                        "new_cards_per_day" => {
+                            config.leitner.new_cards_per_day =
+                                Some(as_positive(section, key, value)? as usize)
+                        }
+                        "initial_box" => {
+                            config.leitner.initial_box = as_positive(section, key, value)?
+                                .try_into()
+                                .map_err(|_| type_error(section, key, "a box number"))?
+                        }
+                        "intervals" => {
+                            config.leitner.intervals = as_intervals(section, key, value)?
+                        }
+                        "initial_delay" => {
+                            config.leitner.initial_delay = match as_integer(section, key, value)? {
+                                n if n >= 0 => n,
+                                _ => {
+                                    return Err(type_error(section, key, "a non-negative integer"))
+                                }
+                            }
+                        }
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+                let boxes = config.leitner.intervals.len();
+                if config.leitner.initial_box as usize > boxes {
+                    let expected = format!("a box from 1 to {}", boxes);
+                    return Err(type_error(section, "initial_box", &expected));
+                }
+            }
+            if let Some(name) = section.strip_prefix("keys.") {
+                let context = Context::from_name(name).ok_or_else(|| ConfigError {
+                    line: 0,
+                    message: format!("unknown key context [{}]", section),
+                })?;
+                for (key, value) in table {
+                    let action = Action::from_name(key).ok_or_else(|| unknown_key(section, key))?;
+                    let specs = match value {
+                        Value::Array(_) => as_string_list(section, key, value)?,
+                        _ => vec![as_string(section, key, value)?],
+                    };
+                    let sequences = specs
+                        .iter()
+                        .map(|spec| Key::parse_sequence(spec))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| {
+                            type_error(section, key, "keys such as \"y\" or \"alt+l\"")
+                        })?;
+                    config.key_bindings.push((context, action, sequences));
+                }
+            }
+            if let Some(name) = section.strip_prefix("dictionaries.") {
+                let mut dictionary = DictionaryConfig::default();
+                for (key, value) in table {
+                    match key.as_str() {
+                        "ansi" => dictionary.ansi = as_bool(section, key, value)?,
+                        "writable" => dictionary.writable = as_bool(section, key, value)?,
+                        "word_column" => {
+                            dictionary.word_column = Some(as_string(section, key, value)?)
+                        }
+                        "definition_column" => {
+                            dictionary.definition_column = Some(as_string(section, key, value)?)
+                        }
+                        "color" => dictionary.color = Some(as_color(section, key, value)?),
+                        "index_order" => dictionary.index_order = as_bool(section, key, value)?,
+                        "stemmer" => {
+                            let language = as_string(section, key, value)?;
+                            dictionary.stemmer =
+                                Some(search::stemmer(&language).ok_or_else(|| {
+                                    type_error(section, key, "a language such as \"english\"")
+                                })?)
+                        }
+                        "newlines" => {
+                            dictionary.newlines = Some(
+                                Newlines::from_name(&as_string(section, key, value)?).ok_or_else(
+                                    || {
+                                        type_error(
+                                            section,
+                                            key,
+                                            "\"none\", \"cr\", \"crlf\" or \"br\"",
+                                        )
+                                    },
+                                )?,
+                            )
+                        }
+                        _ => return Err(unknown_key(section, key)),
+                    }
+                }
+                config.dictionaries.insert(name.to_string(), dictionary);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn dictionary(&self, name: &str) -> &DictionaryConfig {
+        self.dictionaries.get(name).unwrap_or(&DEFAULT_DICTIONARY)
+    }
+}
+
+fn parse_theme(section: &str, table: &BTreeMap<String, Value>) -> Result<Theme, ConfigError> {
+    let mut fg = None;
+    let mut bg = None;
+    let mut highlight = "fill".to_string();
+    let mut monochrome = false;
+    for (key, value) in table {
+        match key.as_str() {
+            "highlight_fg" => fg = Some(as_color(section, key, value)?),
+            "highlight_bg" => bg = Some(as_color(section, key, value)?),
+            "highlight_style" => highlight = as_string(section, key, value)?,
+            "monochrome" => monochrome = as_bool(section, key, value)?,
+            _ => return Err(unknown_key(section, key)),
+        }
+    }
+    let modifier = match highlight.as_str() {
+        "fill" => Modifier::empty(),
+        "bold" => Modifier::BOLD,
+        "underline" => Modifier::UNDERLINED,
+        "reverse" => Modifier::REVERSED,
+        _ => {
+            return Err(type_error(
+                section,
+                "highlight_style",
+                "one of \"fill\", \"bold\", \"underline\" or \"reverse\"",
+            ))
+        }
+    };
+    let (fg, bg) = if modifier.is_empty() {
+        (fg.or(Some(Color::Black)), bg.or(Some(Color::White)))
+    } else {
+        (fg, bg)
+    };
+    let style = Style {
+        fg,
+        bg,
+        add_modifier: modifier,
+        ..Style::default()
+    };
+    let mut theme = Theme {
+        highlight: style,
+        monochrome: false,
+    };
+    if monochrome {
+        theme.make_monochrome();
+    }
+    Ok(theme)
+}
+
+fn unknown_key(section: &str, key: &str) -> ConfigError {
+    ConfigError {
+        line: 0,
+        message: format!("unknown key '{}' in [{}]", key, section),
+    }
+}
+
+fn type_error(section: &str, key: &str, expected: &str) -> ConfigError {
+    ConfigError {
+        line: 0,
+        message: format!("[{}] {}: expected {}", section, key, expected),
+    }
+}
+
+fn as_string(section: &str, key: &str, value: &Value) -> Result<String, ConfigError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        _ => Err(type_error(section, key, "a string")),
+    }
+}
+
+fn as_string_list(section: &str, key: &str, value: &Value) -> Result<Vec<String>, ConfigError> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| as_string(section, key, item))
+            .collect(),
+        _ => Err(type_error(section, key, "an array of strings")),
+    }
+}
+
+/// Days of each Leitner box: positive, never fewer than the box before, and
+/// at most 255 boxes.
+#[cfg(feature = "leitner")]
+fn as_intervals(section: &str, key: &str, value: &Value) -> Result<Vec<u32>, ConfigError> {
+    let expected = "a non-empty array of positive, non-decreasing day counts";
+    let Value::Array(items) = value else {
+        return Err(type_error(section, key, expected));
+    };
+    let intervals = items
+        .iter()
+        .map(|item| match item {
+            Value::Integer(days) => u32::try_from(*days).ok().filter(|&days| days > 0),
+            _ => None,
+        })
+        .collect::<Option<Vec<u32>>>()
+        .filter(|days| !days.is_empty() && days.len() <= u8::MAX as usize)
+        .filter(|days| days.windows(2).all(|pair| pair[0] <= pair[1]));
+    intervals.ok_or_else(|| type_error(section, key, expected))
+}
+
+fn as_color(section: &str, key: &str, value: &Value) -> Result<Color, ConfigError> {
+    let name = as_string(section, key, value)?;
+    Color::from_str(&name).map_err(|_| type_error(section, key, "a color name or #rrggbb"))
+}
+
+fn as_bool(section: &str, key: &str, value: &Value) -> Result<bool, ConfigError> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        _ => Err(type_error(section, key, "a boolean")),
+    }
+}
+
+fn as_integer(section: &str, key: &str, value: &Value) -> Result<i64, ConfigError> {
+    match value {
+        Value::Integer(i) => Ok(*i),
+        _ => Err(type_error(section, key, "an integer")),
+    }
+}
+
+fn as_positive(section: &str, key: &str, value: &Value) -> Result<i64, ConfigError> {
+    match as_integer(section, key, value)? {
+        i if i > 0 => Ok(i),
+        _ => Err(type_error(section, key, "a positive integer")),
+    }
+}
+
+/// Formats `s` as a double-quoted string value.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parses the configuration file, written in TOML, into a `Document`: each
+/// table becomes a section named by its dotted path, e.g.
+/// `dictionaries.english`, holding the keys that are not tables themselves.
+pub fn parse(content: &str) -> Result<Document, ConfigError> {
+    let table: toml::Table = content.parse().map_err(|e: toml::de::Error| ConfigError {
+        line: e
+            .span()
+            .map_or(0, |span| content[..span.start].matches('\n').count() + 1),
+        message: e.message().to_string(),
+    })?;
+    let mut doc = Document::new();
+    add_section(&mut doc, String::new(), table)?;
+    Ok(doc)
+}
+
+fn add_section(doc: &mut Document, section: String, table: toml::Table) -> Result<(), ConfigError> {
+    let mut keys = BTreeMap::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(table) if section.is_empty() => add_section(doc, key, table)?,
+            toml::Value::Table(table) => add_section(doc, format!("{}.{}", section, key), table)?,
+            value => {
+                let value = from_toml(value).ok_or_else(|| {
+                    type_error(&section, &key, "a string, integer, boolean or array")
+                })?;
+                keys.insert(key, value);
+            }
+        }
+    }
+    doc.entry(section).or_default().extend(keys);
+    Ok(())
+}
+
+fn from_toml(value: toml::Value) -> Option<Value> {
+    match value {
+        toml::Value::String(s) => Some(Value::String(s)),
+        toml::Value::Integer(i) => Some(Value::Integer(i)),
+        toml::Value::Boolean(b) => Some(Value::Boolean(b)),
+        toml::Value::Array(items) => items
+            .into_iter()
+            .map(from_toml)
+            .collect::<Option<_>>()
+            .map(Value::Array),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_become_dotted_sections() {
+        let doc = parse(
+            "top = 1\n\
+             [dictionaries]\n\
+             order = [\"english\", 'french']\n\
+             [dictionaries.english]\n\
+             color = \"red\"\n\
+             [dictionaries.\"old words\"]\n\
+             ansi = true\n\
+             [empty]\n",
+        )
+        .unwrap();
+        assert_eq!(doc[""]["top"], Value::Integer(1));
+        assert_eq!(
+            doc["dictionaries"]["order"],
+            Value::Array(vec![
+                Value::String("english".to_string()),
+                Value::String("french".to_string()),
+            ])
+        );
+        assert_eq!(
+            doc["dictionaries.english"]["color"],
+            Value::String("red".to_string())
+        );
+        assert_eq!(doc["dictionaries.old words"]["ansi"], Value::Boolean(true));
+        assert!(doc["empty"].is_empty());
+    }
+
+    #[test]
+    fn arrays_may_span_lines_and_hold_comments() {
+        let doc = parse(
+            "[leitner]\n\
+             intervals = [\n\
+                 1, # one day\n\
+                 3,\n\
+             ] # then three\n\
+             name = \"a # b\" # not part of the value\n",
+        )
+        .unwrap();
+        assert_eq!(
+            doc["leitner"]["intervals"],
+            Value::Array(vec![Value::Integer(1), Value::Integer(3)])
+        );
+        assert_eq!(doc["leitner"]["name"], Value::String("a # b".to_string()));
+    }
+
+    #[test]
+    fn quoted_strings_round_trip() {
+        let name = "say \"hi\"\\\n\tnow\u{1b}";
+        let doc = parse(&format!("name = {}", quote(name))).unwrap();
+        assert_eq!(doc[""]["name"], Value::String(name.to_string()));
+        let doc = parse(r"path = 'C:\dicts'").unwrap();
+        assert_eq!(doc[""]["path"], Value::String(r"C:\dicts".to_string()));
+    }
+
+    #[test]
+    fn study_sheet_settings_do_not_need_the_clipboard() {
+        let doc = parse("[export]\nsheet_format = \"text\"\nsheet_headings = false\n").unwrap();
+        let config = Config::from_document(&doc).unwrap();
+        assert_eq!(config.sheet_format, SheetFormat::Text);
+        assert!(!config.sheet_headings);
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        let error = parse("a = 1\n\nb = \"unterminated\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        let error = parse("[ui]\nwrap = yes\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        let error = parse("[ui]\nratio = 0.5\n").unwrap_err();
+        assert_eq!(error.line, 0);
+        assert_eq!(
+            error.to_string(),
+            "[ui] ratio: expected a string, integer, boolean or array"
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use std::fs;
+#[cfg(feature = "gzip")]
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "gzip")]
+use flate2::read::GzDecoder;
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result};
+
+use crate::config::{Config, DictionaryConfig};
+use crate::schema::{Newlines, Schema};
+use crate::search::{self, SearchMode};
+
+/// First bytes of gzip-compressed data.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Read in place of compressed definitions when the `gzip` feature is off.
+#[cfg(not(feature = "gzip"))]
+const COMPRESSED_DEFINITION: &str =
+    "(compressed definition; build dicrs with the gzip feature to read it)";
+
+/// An entry of a dictionary.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub rowid: i64,
+    pub word: String,
+    pub definition: String,
+    /// Related headwords, from the dictionary's `see_also` column.
+    pub see_also: Vec<String>,
+}
+
+/// A dictionary database: a `dictionary` table of words and definitions.
+pub struct Dictionary {
+    conn: Connection,
+    schema: Schema,
+}
+
+impl Dictionary {
+    /// Opens the dictionary at `path`, read-only unless `config` makes it
+    /// writable.
+    pub fn open(path: &Path, config: &DictionaryConfig) -> Result<Self> {
+        let conn = if config.writable {
+            Connection::open(path)?
+        } else {
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+        };
+        Self::from_connection(conn, config)
+    }
+
+    /// Uses an already open database, e.g. an in-memory one. Registers the
+    /// `dicrs_fold` SQL function, which folds text like `search::fold`.
+    pub fn from_connection(conn: Connection, config: &DictionaryConfig) -> Result<Self> {
+        conn.create_scalar_function(
+            "dicrs_fold",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                Ok(ctx
+                    .get::<Option<String>>(0)?
+                    .map(|text| search::fold(&text)))
+            },
+        )?;
+        let schema = Schema::detect(&conn, config);
+        Ok(Self { conn, schema })
+    }
+
+    /// Number of entries.
+    pub fn count(&self) -> Result<usize> {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM dictionary", [], |row| row.get(0))
+    }
+
+    /// Row ids and words of all entries, in the order they are stored. A
+    /// NULL word is read as an empty string.
+    pub fn index(&self) -> Result<(Vec<i64>, Vec<String>)> {
+        self.index_after(i64::MIN, usize::MAX)
+    }
+
+    /// Like `index`, but only the first `limit` entries whose row id is
+    /// greater than `rowid`, so that a large index can be read in chunks.
+    pub fn index_after(&self, rowid: i64, limit: usize) -> Result<(Vec<i64>, Vec<String>)> {
+        let mut stmt = self.conn.prepare(&self.schema.sql(
+            "SELECT ROWID, {word} FROM dictionary WHERE ROWID > ?1 ORDER BY ROWID LIMIT ?2",
+        ))?;
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        let rows = stmt.query_map(params![rowid, limit], |row| {
+            Ok((
+                row.get(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            ))
+        })?;
+        let mut rowids = Vec::new();
+        let mut words = Vec::new();
+        for row in rows {
+            let (rowid, word) = row?;
+            rowids.push(rowid);
+            words.push(word);
+        }
+        Ok((rowids, words))
+    }
+
+    /// The entry with the given row id, with the line breaks of the
+    /// definition turned into `\n` as the schema says. NULL words and
+    /// definitions are read as empty strings, definitions stored as BLOBs are
+    /// decoded as described at `definition_value`, and the `see_also` column
+    /// is split at commas.
+    pub fn entry(&self, rowid: i64) -> Result<Option<Entry>> {
+        self.conn
+            .query_row(
+                &self.schema.sql(
+                    "SELECT {word}, {definition}, {see_also} FROM dictionary WHERE ROWID = ?1",
+                ),
+                [rowid],
+                |row| {
+                    Ok(Entry {
+                        rowid,
+                        word: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                        definition: self
+                            .schema
+                            .newlines
+                            .apply(&definition_value(row.get_ref(1)?)),
+                        see_also: row
+                            .get::<_, Option<String>>(2)?
+                            .unwrap_or_default()
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|word| !word.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// The first entry whose word matches `query` in the given search mode.
+    pub fn lookup(&self, query: &str, mode: SearchMode) -> Result<Option<Entry>> {
+        match self.search(query, mode, 1)?.first() {
+            Some(&(rowid, _)) => self.entry(rowid),
+            None => Ok(None),
+        }
+    }
+
+    /// Row ids and words of up to `limit` entries whose word matches `query`
+    /// in the given search mode, ignoring case and diacritics. Fuzzy and regex
+    /// searches go over the whole index, fuzzy matches the closest first.
+    pub fn search(
+        &self,
+        query: &str,
+        mode: SearchMode,
+        limit: usize,
+    ) -> Result<Vec<(i64, String)>> {
+        if mode.needs_index(query) {
+            let (rowids, words) = self.index()?;
+            let folded: Vec<String> = words.iter().map(|word| search::fold(word)).collect();
+            let matches = search::all_matches(&folded, &mode.fold(query), mode, false);
+            return Ok(matches
+                .into_iter()
+                .take(limit)
+                .map(|i| (rowids[i], words[i].clone()))
+                .collect());
+        }
+        let mut stmt = self.conn.prepare(&self.schema.sql(
+            "SELECT ROWID, {word} FROM dictionary WHERE dicrs_fold({word}) LIKE ?1 ESCAPE '\\' ORDER BY ROWID LIMIT ?2",
+        ))?;
+        let pattern = mode.like_pattern(&mode.fold(query));
+        let rows = stmt.query_map(params![pattern, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// How line breaks are stored in the definitions.
+    pub fn newlines(&self) -> Newlines {
+        self.schema.newlines
+    }
+
+    /// The definition of an entry exactly as stored, empty when NULL and
+    /// decompressed when gzip-compressed.
+    pub fn raw_definition(&self, rowid: i64) -> Result<Option<String>> {
+        self.conn
+            .query_row(
+                &self
+                    .schema
+                    .sql("SELECT {definition} FROM dictionary WHERE ROWID = ?1"),
+                [rowid],
+                |row| Ok(definition_value(row.get_ref(0)?)),
+            )
+            .optional()
+    }
+
+    /// Row ids of the first `limit` entries whose definition matches
+    /// `pattern`, in storage order.
+    pub fn matching_definitions(&self, pattern: &Regex, limit: usize) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            &self
+                .schema
+                .sql("SELECT ROWID, {definition} FROM dictionary ORDER BY ROWID"),
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut rowids = Vec::new();
+        while rowids.len() < limit {
+            let Some(row) = rows.next()? else {
+                break;
+            };
+            let value = row.get_ref(1)?;
+            if value != ValueRef::Null && pattern.is_match(&definition_value(value)) {
+                rowids.push(row.get(0)?);
+            }
+        }
+        Ok(rowids)
+    }
+
+    /// Calls `f` with the row id and definition of each entry that has one,
+    /// in storage order, with line breaks turned into `\n` as in `entry`.
+    pub fn for_each_definition(&self, mut f: impl FnMut(i64, &str) -> Result<()>) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            &self
+                .schema
+                .sql("SELECT ROWID, {definition} FROM dictionary ORDER BY ROWID"),
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let value = row.get_ref(1)?;
+            if value != ValueRef::Null {
+                let definition = self.schema.newlines.apply(&definition_value(value));
+                f(row.get(0)?, &definition)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an entry has exactly this word.
+    pub fn contains(&self, word: &str) -> Result<bool> {
+        self.conn.query_row(
+            &self
+                .schema
+                .sql("SELECT EXISTS(SELECT 1 FROM dictionary WHERE {word} = ?1)"),
+            [word],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn insert(&self, word: &str, definition: &str) -> Result<()> {
+        self.conn.execute(
+            &self
+                .schema
+                .sql("INSERT INTO dictionary ({word}, {definition}) VALUES (?1, ?2)"),
+            [word, definition],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the definition of the entries with exactly this word.
+    pub fn replace_definition(&self, word: &str, definition: &str) -> Result<()> {
+        self.conn.execute(
+            &self
+                .schema
+                .sql("UPDATE dictionary SET {definition} = ?2 WHERE {word} = ?1"),
+            [word, definition],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_definition(&self, rowid: i64, definition: &str) -> Result<()> {
+        self.conn.execute(
+            &self
+                .schema
+                .sql("UPDATE dictionary SET {definition} = ?1 WHERE ROWID = ?2"),
+            params![definition, rowid],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&self, rowid: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM dictionary WHERE ROWID = ?1", [rowid])?;
+        Ok(())
+    }
+}
+
+/// Reads a definition column as text. NULL is read as an empty string, and a
+/// BLOB as UTF-8 text, after decompressing it when it starts with the gzip
+/// magic bytes.
+fn definition_value(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(number) => number.to_string(),
+        ValueRef::Real(number) => number.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(blob) if blob.starts_with(&GZIP_MAGIC) => {
+            #[cfg(feature = "gzip")]
+            {
+                let mut text = String::new();
+                if GzDecoder::new(blob).read_to_string(&mut text).is_ok() {
+                    return text;
+                }
+                String::from_utf8_lossy(blob).into_owned()
+            }
+            #[cfg(not(feature = "gzip"))]
+            COMPRESSED_DEFINITION.to_string()
+        }
+        ValueRef::Blob(blob) => String::from_utf8_lossy(blob).into_owned(),
+    }
+}
+
+/// Joins the definitions of entries sharing a headword, with `separator` on
+/// its own line between them, optionally numbering them "1.", "2.", ...
+pub fn merge_senses(definitions: &[String], separator: &str, numbered: bool) -> String {
+    let separator = if separator.is_empty() {
+        "\n\n".to_string()
+    } else {
+        format!("\n{}\n", separator)
+    };
+    definitions
+        .iter()
+        .enumerate()
+        .map(|(i, definition)| match numbered {
+            true => format!("{}. {}", i + 1, definition),
+            false => definition.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(&separator)
+}
+
+/// Finds the dictionaries in `dicpath` and the configured folders. Returns
+/// their names, with the folder appended to names found more than once, and
+/// their paths, in the configured order and then alphabetically.
+pub fn scan(dicpath: &Path, config: &Config) -> (Vec<String>, Vec<PathBuf>) {
+    let mut databases: Vec<(String, PathBuf)> = Vec::new();
+    for dir in std::iter::once(dicpath).chain(config.directories.iter().map(PathBuf::as_path)) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(filename) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let name = config
+                .extensions
+                .iter()
+                .find_map(|ext| filename.strip_suffix(ext.as_str()))
+                .filter(|name| !name.is_empty());
+            if let Some(name) = name {
+                databases.push((name.to_string(), path.clone()));
+            }
+        }
+    }
+    let names: Vec<String> = databases.iter().map(|(name, _)| name.clone()).collect();
+    for (name, path) in databases.iter_mut() {
+        if names.iter().filter(|n| *n == name).count() > 1 {
+            let dir = path
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            *name = format!("{} ({})", name, dir);
+        }
+    }
+    let order = &config.dictionary_order;
+    databases.sort_by_cached_key(|(name, _)| {
+        let position = order.iter().position(|o| o == name);
+        (position.unwrap_or(usize::MAX), name.to_lowercase())
+    });
+    databases.into_iter().unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Dictionary {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dictionary (word TEXT, definition TEXT);
+             INSERT INTO dictionary VALUES
+                 ('apple', 'A fruit.'),
+                 ('Apply', 'To put to use.'),
+                 ('cat', 'An animal.\rA small one.'),
+                 ('100%', 'All of it.');",
+        )
+        .unwrap();
+        Dictionary::from_connection(conn, &DictionaryConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn index_lists_every_entry_in_storage_order() {
+        let (rowids, words) = fixture().index().unwrap();
+        assert_eq!(rowids, [1, 2, 3, 4]);
+        assert_eq!(words, ["apple", "Apply", "cat", "100%"]);
+    }
+
+    #[test]
+    fn the_index_can_be_read_in_chunks() {
+        let dictionary = fixture();
+        assert_eq!(dictionary.count().unwrap(), 4);
+        let (rowids, words) = dictionary.index_after(i64::MIN, 3).unwrap();
+        assert_eq!(rowids, [1, 2, 3]);
+        assert_eq!(words, ["apple", "Apply", "cat"]);
+        let (rowids, words) = dictionary.index_after(3, 3).unwrap();
+        assert_eq!(rowids, [4]);
+        assert_eq!(words, ["100%"]);
+        assert!(dictionary.index_after(4, 3).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn entries_are_read_by_rowid() {
+        let dictionary = fixture();
+        let entry = dictionary.entry(3).unwrap().unwrap();
+        assert_eq!(entry.word, "cat");
+        assert_eq!(entry.definition, "An animal.\nA small one.");
+        assert_eq!(
+            dictionary.raw_definition(3).unwrap().as_deref(),
+            Some("An animal.\rA small one.")
+        );
+        assert_eq!(dictionary.entry(99).unwrap(), None);
+    }
+
+    #[test]
+    fn related_headwords_are_read_from_see_also() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dictionary (word TEXT, definition TEXT, see_also TEXT);
+             INSERT INTO dictionary VALUES
+                 ('big', 'Large.', 'large, huge,'),
+                 ('small', 'Little.', NULL);",
+        )
+        .unwrap();
+        let dictionary = Dictionary::from_connection(conn, &DictionaryConfig::default()).unwrap();
+        assert_eq!(
+            dictionary.entry(1).unwrap().unwrap().see_also,
+            ["large", "huge"]
+        );
+        assert!(dictionary.entry(2).unwrap().unwrap().see_also.is_empty());
+        assert!(fixture().entry(1).unwrap().unwrap().see_also.is_empty());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compressed_definitions_are_decompressed() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all("Packed.\rSmall.".as_bytes()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE dictionary (word TEXT, definition BLOB)")
+            .unwrap();
+        conn.execute(
+            "INSERT INTO dictionary VALUES ('zip', ?1), ('plain', CAST('As is.' AS BLOB))",
+            [encoder.finish().unwrap()],
+        )
+        .unwrap();
+        let dictionary = Dictionary::from_connection(conn, &DictionaryConfig::default()).unwrap();
+        assert_eq!(
+            dictionary.entry(1).unwrap().unwrap().definition,
+            "Packed.\nSmall."
+        );
+        assert_eq!(dictionary.entry(2).unwrap().unwrap().definition, "As is.");
+        let pattern = Regex::new("Small").unwrap();
+        assert_eq!(
+            dictionary
+                .matching_definitions(&pattern, usize::MAX)
+                .unwrap(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn search_follows_the_mode_and_escapes_wildcards() {
+        let dictionary = fixture();
+        let words = |query, mode| -> Vec<String> {
+            dictionary
+                .search(query, mode, 10)
+                .unwrap()
+                .into_iter()
+                .map(|(_, word)| word)
+                .collect()
+        };
+        assert_eq!(words("app", SearchMode::Prefix), ["apple", "Apply"]);
+        assert_eq!(words("pl", SearchMode::Contains), ["apple", "Apply"]);
+        assert_eq!(words("apply", SearchMode::Exact), ["Apply"]);
+        assert_eq!(words("100%", SearchMode::Exact), ["100%"]);
+        assert!(words("%", SearchMode::Exact).is_empty());
+        assert_eq!(words("aple", SearchMode::Fuzzy), ["apple"]);
+        assert_eq!(words("a*e", SearchMode::Contains), ["apple"]);
+        assert_eq!(words("app?y", SearchMode::Fuzzy), ["Apply"]);
+        assert_eq!(words("1?0%", SearchMode::Prefix), ["100%"]);
+        assert_eq!(words("^A.+E$", SearchMode::Regex), ["apple"]);
+        assert_eq!(words("ÀPPLÉ", SearchMode::Exact), ["apple"]);
+        assert_eq!(words("app", SearchMode::Phrase), ["apple", "Apply"]);
+        let entry = dictionary
+            .lookup("ca", SearchMode::Prefix)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.word, "cat");
+    }
+
+    #[test]
+    fn null_words_and_definitions_are_read_as_empty() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dictionary (word TEXT, definition TEXT);
+             INSERT INTO dictionary VALUES (NULL, 'Orphan.'), ('blank', NULL);",
+        )
+        .unwrap();
+        let dictionary = Dictionary::from_connection(conn, &DictionaryConfig::default()).unwrap();
+        assert_eq!(dictionary.index().unwrap().1, ["", "blank"]);
+        assert_eq!(dictionary.entry(1).unwrap().unwrap().word, "");
+        assert_eq!(dictionary.entry(2).unwrap().unwrap().definition, "");
+        assert_eq!(dictionary.raw_definition(2).unwrap().as_deref(), Some(""));
+        let pattern = Regex::new(".").unwrap();
+        assert_eq!(
+            dictionary
+                .matching_definitions(&pattern, usize::MAX)
+                .unwrap(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn definitions_are_searched_with_a_pattern() {
+        let dictionary = fixture();
+        let pattern = Regex::new(r"(?i)^an? ").unwrap();
+        assert_eq!(
+            dictionary
+                .matching_definitions(&pattern, usize::MAX)
+                .unwrap(),
+            [1, 3]
+        );
+        assert_eq!(dictionary.matching_definitions(&pattern, 1).unwrap(), [1]);
+    }
+
+    #[test]
+    fn senses_are_joined_with_the_separator() {
+        let senses = ["first".to_string(), "second".to_string()];
+        assert_eq!(merge_senses(&senses, "", false), "first\n\nsecond");
+        assert_eq!(
+            merge_senses(&senses, "---", true),
+            "1. first\n---\n2. second"
+        );
+    }
+
+    #[test]
+    fn entries_can_be_added_changed_and_deleted() {
+        let dictionary = fixture();
+        dictionary.insert("dog", "Another animal.").unwrap();
+        assert!(dictionary.contains("dog").unwrap());
+        dictionary.replace_definition("dog", "A pet.").unwrap();
+        assert_eq!(dictionary.entry(5).unwrap().unwrap().definition, "A pet.");
+        dictionary.set_definition(5, "A loyal pet.").unwrap();
+        assert_eq!(
+            dictionary.raw_definition(5).unwrap().as_deref(),
+            Some("A loyal pet.")
+        );
+        dictionary.delete(5).unwrap();
+        assert!(!dictionary.contains("dog").unwrap());
+    }
+
+    #[test]
+    fn editing_one_entry_keeps_others_with_the_same_headword() {
+        let dictionary = fixture();
+        dictionary.insert("cat", "A second sense.").unwrap();
+        dictionary.set_definition(5, "An edited sense.").unwrap();
+        assert_eq!(
+            dictionary.raw_definition(3).unwrap().as_deref(),
+            Some("An animal.\rA small one.")
+        );
+        assert_eq!(
+            dictionary.raw_definition(5).unwrap().as_deref(),
+            Some("An edited sense.")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use rusqlite::{params, Connection, Result};
+
+/// File format of a study sheet of favorites.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SheetFormat {
+    #[default]
+    Markdown,
+    Text,
+}
+
+impl SheetFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "markdown" => Some(SheetFormat::Markdown),
+            "text" => Some(SheetFormat::Text),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SheetFormat::Markdown => "md",
+            SheetFormat::Text => "txt",
+        }
+    }
+}
+
+/// Words marked as favorites, per dictionary.
+pub struct Favorites {
+    conn: Connection,
+}
+
+impl Favorites {
+    pub fn new(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS favorites (
+                dictionary TEXT NOT NULL,
+                word TEXT NOT NULL,
+                PRIMARY KEY (dictionary, word)
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Adds or removes `word` from the favorites of `dictionary`. Returns
+    /// whether it is a favorite afterwards.
+    pub fn toggle(&self, dictionary: &str, word: &str) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM favorites WHERE dictionary = ?1 AND word = ?2",
+            params![dictionary, word],
+        )?;
+        if removed > 0 {
+            return Ok(false);
+        }
+        self.conn.execute(
+            "INSERT INTO favorites (dictionary, word) VALUES (?1, ?2)",
+            params![dictionary, word],
+        )?;
+        Ok(true)
+    }
+
+    pub fn contains(&self, dictionary: &str, word: &str) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM favorites WHERE dictionary = ?1 AND word = ?2)",
+            params![dictionary, word],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn words(&self, dictionary: &str) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT word FROM favorites WHERE dictionary = ?1")?;
+        let rows = stmt.query_map([dictionary], |row| row.get(0))?;
+        rows.collect()
+    }
+}
+
+/// Formats words and their definitions as a printable study sheet titled
+/// `title`, with each headword on its own heading line or leading its
+/// definition.
+pub fn study_sheet(
+    title: &str,
+    entries: &[(String, String)],
+    format: SheetFormat,
+    headings: bool,
+) -> String {
+    let mut sheet = match format {
+        SheetFormat::Markdown => format!("# {}\n\n", title),
+        SheetFormat::Text => format!("{}\n{}\n\n", title, "=".repeat(title.chars().count())),
+    };
+    for (word, definition) in entries {
+        let entry = match (format, headings) {
+            (SheetFormat::Markdown, true) => format!("## {}\n\n{}", word, definition),
+            (SheetFormat::Markdown, false) => format!("**{}**: {}", word, definition),
+            (SheetFormat::Text, true) => format!(
+                "{}\n{}\n{}",
+                word,
+                "-".repeat(word.chars().count()),
+                definition
+            ),
+            (SheetFormat::Text, false) => format!("{}: {}", word, definition),
+        };
+        sheet.push_str(entry.trim_end());
+        sheet.push_str("\n\n");
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn study_sheets_follow_the_format() {
+        let entries = [("cat".to_string(), "An animal.".to_string())];
+        assert_eq!(
+            study_sheet("Favorites", &entries, SheetFormat::Markdown, true),
+            "# Favorites\n\n## cat\n\nAn animal.\n\n"
+        );
+        assert_eq!(
+            study_sheet("Favorites", &entries, SheetFormat::Text, false),
+            "Favorites\n=========\n\ncat: An animal.\n\n"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::Dictionary;
+
+/// An FTS5 index of the definitions of one dictionary. It is kept in its own
+/// database, so that dictionaries can stay read-only, and stores no copy of
+/// the definitions: matches are row ids of the dictionary.
+pub struct FullText {
+    conn: Connection,
+}
+
+impl FullText {
+    /// Opens the index at `path`, creating an empty one if there is none.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS definitions USING fts5(definition, content='');
+             CREATE TABLE IF NOT EXISTS source (stamp TEXT NOT NULL);",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Whether the index was built from the dictionary file with this
+    /// `stamp`.
+    pub fn is_current(&self, stamp: &str) -> Result<bool> {
+        let built: Option<String> = self
+            .conn
+            .query_row("SELECT stamp FROM source", [], |row| row.get(0))
+            .optional()?;
+        Ok(built.as_deref() == Some(stamp))
+    }
+
+    /// Replaces the index with the definitions of `dictionary`, whose file
+    /// has the given `stamp`. Returns the number of definitions indexed.
+    pub fn build(&mut self, dictionary: &Dictionary, stamp: &str) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO definitions(definitions) VALUES ('delete-all')",
+            [],
+        )?;
+        let mut count = 0;
+        {
+            let mut insert =
+                tx.prepare("INSERT INTO definitions (rowid, definition) VALUES (?1, ?2)")?;
+            dictionary.for_each_definition(|rowid, definition| {
+                insert.execute(params![rowid, definition])?;
+                count += 1;
+                Ok(())
+            })?;
+        }
+        tx.execute("DELETE FROM source", [])?;
+        tx.execute("INSERT INTO source (stamp) VALUES (?1)", [stamp])?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Row ids of up to `limit` entries whose definition has all the words
+    /// of `query`, the most relevant first. See `match_expression`.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<i64>> {
+        let expression = match_expression(query);
+        if expression.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT rowid FROM definitions WHERE definitions MATCH ?1 ORDER BY rank LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![expression, limit as i64], |row| row.get(0))?;
+        rows.collect()
+    }
+}
+
+/// Identifies the current version of a dictionary file by its path, size and
+/// modification time, so that an index built from another one is rebuilt.
+pub fn stamp(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(metadata) = fs::metadata(&path) else {
+        return String::new();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    format!("{}:{}:{}", path.display(), metadata.len(), modified)
+}
+
+/// Turns the words of `query` into an FTS5 expression matching definitions
+/// that contain all of them. Each word is quoted, so punctuation is not read
+/// as query syntax, and a word ending in `*` matches as a prefix.
+fn match_expression(query: &str) -> String {
+    query
+        .split_whitespace()
+        .filter_map(|word| {
+            let (word, prefix) = match word.strip_suffix('*') {
+                Some(stem) => (stem, "*"),
+                None => (word, ""),
+            };
+            (!word.is_empty()).then(|| format!("\"{}\"{}", word.replace('"', "\"\""), prefix))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DictionaryConfig;
+
+    fn dictionary() -> Dictionary {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dictionary (word TEXT, definition TEXT);
+             INSERT INTO dictionary VALUES
+                 ('cat', 'A small animal that hunts mice at night.'),
+                 ('dog', 'A pet; a loyal animal, a clever animal.'),
+                 ('tree', 'A plant.'),
+                 ('empty', NULL);",
+        )
+        .unwrap();
+        Dictionary::from_connection(conn, &DictionaryConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn definitions_are_ranked_by_relevance() {
+        let mut full_text = FullText::open(Path::new(":memory:")).unwrap();
+        assert!(!full_text.is_current("1:2").unwrap());
+        assert_eq!(full_text.build(&dictionary(), "1:2").unwrap(), 3);
+        assert!(full_text.is_current("1:2").unwrap());
+        assert_eq!(full_text.search("animal", 10).unwrap(), [2, 1]);
+        assert_eq!(full_text.search("small animal", 10).unwrap(), [1]);
+        assert_eq!(full_text.search("pla*", 10).unwrap(), [3]);
+        assert_eq!(full_text.search("\"pet;", 10).unwrap(), [2]);
+        assert!(full_text.search("  ", 10).unwrap().is_empty());
+        full_text.build(&dictionary(), "3:4").unwrap();
+        assert_eq!(full_text.search("animal", 1).unwrap(), [2]);
+    }
+}
@@ -0,0 +1,99 @@
+use ratatui::style::{Color, Modifier};
+use ratatui::text::Text;
+
+/// Converts a styled definition into a standalone HTML snippet. Bold, italic,
+/// underline and strike-through become tags, colors become inline styles and
+/// line breaks become `<br>`.
+pub fn from_text(word: &str, text: &Text) -> String {
+    let mut html = format!(
+        "<div class=\"dicrs-entry\">\n<h3>{}</h3>\n<p>\n",
+        escape(word)
+    );
+    for (i, line) in text.lines.iter().enumerate() {
+        if i > 0 {
+            html.push_str("<br>\n");
+        }
+        for span in &line.spans {
+            let style = span.style;
+            let mut open = String::new();
+            let mut close = String::new();
+            let mut css = Vec::new();
+            if let Some(color) = style.fg.and_then(css_color) {
+                css.push(format!("color: {}", color));
+            }
+            if let Some(color) = style.bg.and_then(css_color) {
+                css.push(format!("background-color: {}", color));
+            }
+            if !css.is_empty() {
+                open.push_str(&format!("<span style=\"{}\">", css.join("; ")));
+                close.insert_str(0, "</span>");
+            }
+            for (modifier, tag) in [
+                (Modifier::BOLD, "b"),
+                (Modifier::ITALIC, "i"),
+                (Modifier::UNDERLINED, "u"),
+                (Modifier::CROSSED_OUT, "s"),
+            ] {
+                if style.add_modifier.contains(modifier) {
+                    open.push_str(&format!("<{}>", tag));
+                    close.insert_str(0, &format!("</{}>", tag));
+                }
+            }
+            html.push_str(&open);
+            html.push_str(&escape(&span.content));
+            html.push_str(&close);
+        }
+    }
+    html.push_str("\n</p>\n</div>\n");
+    html
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn css_color(color: Color) -> Option<String> {
+    const PALETTE: [&str; 16] = [
+        "black", "maroon", "green", "olive", "navy", "purple", "teal", "silver", "gray", "red",
+        "lime", "yellow", "blue", "fuchsia", "aqua", "white",
+    ];
+    let name = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        Color::Indexed(i) => return PALETTE.get(i as usize).map(|name| name.to_string()),
+        Color::Black => "black",
+        Color::Red => "maroon",
+        Color::Green => "green",
+        Color::Yellow => "olive",
+        Color::Blue => "navy",
+        Color::Magenta => "purple",
+        Color::Cyan => "teal",
+        Color::Gray => "silver",
+        Color::DarkGray => "gray",
+        Color::LightRed => "red",
+        Color::LightGreen => "lime",
+        Color::LightYellow => "yellow",
+        Color::LightBlue => "blue",
+        Color::LightMagenta => "fuchsia",
+        Color::LightCyan => "aqua",
+        Color::White => "white",
+    };
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_styles_and_line_breaks() {
+        let text = crate::ansi::to_text("\x1b[1mbold\x1b[0m & <plain>\n\x1b[31mred\x1b[0m");
+        let html = from_text("word", &text);
+        assert!(html.contains("<h3>word</h3>"));
+        assert!(html.contains("<b>bold</b> &amp; &lt;plain&gt;<br>"));
+        assert!(html.contains("<span style=\"color: maroon\">red</span>"));
+    }
+}
@@ -0,0 +1,799 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::Config;
+
+/// A single key press, normalized so that shifted characters compare equal
+/// regardless of whether the terminal reports the SHIFT modifier.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+}
+
+impl Key {
+    /// Parses a key such as `y`, `enter`, `alt+l` or `ctrl+shift+up`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        loop {
+            let lower = rest.to_lowercase();
+            let (modifier, len) = if lower.starts_with("ctrl+") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt+") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift+") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+            modifiers |= modifier;
+            rest = &rest[len..];
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        if let KeyCode::Char(_) = code {
+            modifiers -= KeyModifiers::SHIFT;
+        }
+        Some(Self::new(code, modifiers))
+    }
+
+    /// Parses a space separated key sequence such as `g g`.
+    pub fn parse_sequence(spec: &str) -> Option<Vec<Self>> {
+        let keys: Option<Vec<Self>> = spec.split_whitespace().map(Self::parse).collect();
+        keys.filter(|keys| !keys.is_empty())
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl"),
+            (KeyModifiers::ALT, "Alt"),
+            (KeyModifiers::SHIFT, "Shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{} + ", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_uppercase()),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            code => write!(f, "{}", code),
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        Self::new(event.code, modifiers)
+    }
+}
+
+/// Where a binding applies.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Context {
+    /// Default and Minimal modes, both while typing and in normal mode.
+    Browse,
+    /// Only in normal mode, where plain letters are commands instead of input.
+    Normal,
+    #[cfg(feature = "leitner")]
+    Leitner,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    #[cfg(feature = "clipboard")]
+    CopyDefinition,
+    #[cfg(feature = "clipboard")]
+    CopyMarkdown,
+    ToggleMinimal,
+    Search,
+    SearchAll,
+    ToggleSearchAll,
+    ToggleFilter,
+    SearchDefinitions,
+    ReverseSearch,
+    ToggleFullText,
+    ToggleFavorite,
+    FavoritesOnly,
+    ExportFavorites,
+    Refresh,
+    ToggleRaw,
+    ToggleFold,
+    NextLink,
+    FollowLink,
+    ExportHtml,
+    Pronounce,
+    CycleSearchMode,
+    ToggleWholeWord,
+    ToggleLiveSearch,
+    Validate,
+    EditDefinition,
+    AddEntry,
+    EditEntry,
+    DeleteEntry,
+    Up,
+    Down,
+    JumpUp,
+    JumpDown,
+    Top,
+    Bottom,
+    JumpPercent,
+    PreviousDictionary,
+    NextDictionary,
+    AlternateDictionary,
+    RecentDictionary,
+    Back,
+    Forward,
+    PreviousDictionaryWithWord,
+    NextDictionaryWithWord,
+    ScrollUp,
+    ScrollDown,
+    DeleteChar,
+    DeleteWord,
+    NormalMode,
+    InsertMode,
+    RecordMacro,
+    PlayMacro,
+    #[cfg(feature = "leitner")]
+    EnterLeitner,
+    #[cfg(feature = "leitner")]
+    AddToLeitner,
+    #[cfg(feature = "leitner")]
+    ShowInLeitner,
+    #[cfg(feature = "leitner")]
+    ReviewCorrect,
+    #[cfg(feature = "leitner")]
+    ReviewIncorrect,
+    #[cfg(feature = "leitner")]
+    ShowCardDefinition,
+    #[cfg(feature = "leitner")]
+    ExportAnki,
+    #[cfg(feature = "leitner")]
+    LeaveLeitner,
+    #[cfg(feature = "leitner")]
+    LeitnerToMinimal,
+    Help,
+    About,
+    CommandPalette,
+}
+
+impl Context {
+    /// Looks up a context by the name used in the `[keys.<context>]` config sections.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "browse" => Some(Context::Browse),
+            "normal" => Some(Context::Normal),
+            #[cfg(feature = "leitner")]
+            "leitner" => Some(Context::Leitner),
+            _ => None,
+        }
+    }
+}
+
+/// Config name and help description of every action.
+static ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::Quit, "quit", "Exit the application"),
+    #[cfg(feature = "clipboard")]
+    (
+        Action::CopyDefinition,
+        "copy_definition",
+        "Copy the definition to the clipboard",
+    ),
+    #[cfg(feature = "clipboard")]
+    (
+        Action::CopyMarkdown,
+        "copy_markdown",
+        "Copy the entry as a Markdown quote",
+    ),
+    (
+        Action::ToggleMinimal,
+        "toggle_minimal",
+        "Toggle the minimal mode",
+    ),
+    (Action::Search, "search", "Search for the input"),
+    (
+        Action::SearchAll,
+        "search_all",
+        "Search the input in all dictionaries",
+    ),
+    (
+        Action::ToggleSearchAll,
+        "toggle_search_all",
+        "Toggle searching all dictionaries with Enter",
+    ),
+    (
+        Action::ToggleFilter,
+        "toggle_filter",
+        "Filter the index by the input",
+    ),
+    (
+        Action::ToggleFavorite,
+        "toggle_favorite",
+        "Add or remove the word from the favorites",
+    ),
+    (
+        Action::FavoritesOnly,
+        "favorites_only",
+        "Show only the favorites in the index",
+    ),
+    (
+        Action::ExportFavorites,
+        "export_favorites",
+        "Write the favorites and their definitions to a study sheet",
+    ),
+    (
+        Action::Refresh,
+        "refresh",
+        "Reload the dictionary from disk",
+    ),
+    (
+        Action::ToggleRaw,
+        "toggle_raw",
+        "Show the definition exactly as stored",
+    ),
+    (
+        Action::ExportHtml,
+        "export_html",
+        "Export the entry as HTML",
+    ),
+    (
+        Action::ToggleFold,
+        "toggle_fold",
+        "Expand or fold a long definition",
+    ),
+    (
+        Action::NextLink,
+        "next_link",
+        "Select the next suggestion or related headword",
+    ),
+    (
+        Action::FollowLink,
+        "follow_link",
+        "Look up the selected related headword",
+    ),
+    (Action::Pronounce, "pronounce", "Pronounce the word"),
+    (
+        Action::CycleSearchMode,
+        "cycle_search_mode",
+        "Cycle the search mode",
+    ),
+    (
+        Action::SearchDefinitions,
+        "search_definitions",
+        "Filter the index by definitions matching the input as a regex",
+    ),
+    (
+        Action::ReverseSearch,
+        "reverse_search",
+        "List the entries whose definition contains the input",
+    ),
+    (
+        Action::ToggleFullText,
+        "toggle_full_text",
+        "Search the definitions instead of the headwords, by relevance",
+    ),
+    (
+        Action::ToggleWholeWord,
+        "toggle_whole_word",
+        "Toggle matching whole words in contains and reverse searches",
+    ),
+    (
+        Action::ToggleLiveSearch,
+        "toggle_live_search",
+        "Toggle looking the input up as it is typed",
+    ),
+    (
+        Action::Validate,
+        "validate",
+        "Check the dictionary for problems",
+    ),
+    (
+        Action::EditDefinition,
+        "edit_definition",
+        "Edit the definition in $EDITOR",
+    ),
+    (
+        Action::AddEntry,
+        "add_entry",
+        "Add an entry to the dictionary",
+    ),
+    (
+        Action::EditEntry,
+        "edit_entry",
+        "Edit the definition in place",
+    ),
+    (Action::DeleteEntry, "delete_entry", "Delete the entry"),
+    (Action::Up, "up", "Move the selection up"),
+    (Action::Down, "down", "Move the selection down"),
+    (Action::JumpUp, "jump_up", "Move the selection up by 10"),
+    (
+        Action::JumpDown,
+        "jump_down",
+        "Move the selection down by 10",
+    ),
+    (Action::Top, "top", "Go to the first entry"),
+    (Action::Bottom, "bottom", "Go to the last entry"),
+    (
+        Action::JumpPercent,
+        "jump_percent",
+        "Jump to a percentage of the index",
+    ),
+    (
+        Action::PreviousDictionary,
+        "previous_dictionary",
+        "Switch to the previous dictionary",
+    ),
+    (
+        Action::NextDictionary,
+        "next_dictionary",
+        "Switch to the next dictionary",
+    ),
+    (
+        Action::AlternateDictionary,
+        "alternate_dictionary",
+        "Switch back to the previously used dictionary",
+    ),
+    (
+        Action::RecentDictionary,
+        "recent_dictionary",
+        "Cycle through the recently used dictionaries",
+    ),
+    (
+        Action::Back,
+        "back",
+        "Go back to the previously visited word",
+    ),
+    (
+        Action::Forward,
+        "forward",
+        "Go forward to the next visited word",
+    ),
+    (
+        Action::PreviousDictionaryWithWord,
+        "previous_dictionary_with_word",
+        "Switch to the previous dictionary that has the word",
+    ),
+    (
+        Action::NextDictionaryWithWord,
+        "next_dictionary_with_word",
+        "Switch to the next dictionary that has the word",
+    ),
+    (Action::ScrollUp, "scroll_up", "Scroll the definition up"),
+    (
+        Action::ScrollDown,
+        "scroll_down",
+        "Scroll the definition down",
+    ),
+    (
+        Action::DeleteChar,
+        "delete_char",
+        "Delete the last character of the input",
+    ),
+    (
+        Action::DeleteWord,
+        "delete_word",
+        "Delete the last word of the input",
+    ),
+    (Action::NormalMode, "normal_mode", "Enter normal mode"),
+    (Action::InsertMode, "insert_mode", "Leave normal mode"),
+    (
+        Action::RecordMacro,
+        "record_macro",
+        "Start or stop recording a macro",
+    ),
+    (Action::PlayMacro, "play_macro", "Replay the recorded macro"),
+    #[cfg(feature = "leitner")]
+    (
+        Action::EnterLeitner,
+        "enter_leitner",
+        "Switch to Leitner mode",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::AddToLeitner,
+        "add_to_leitner",
+        "Add the word to Leitner",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::ShowInLeitner,
+        "show_in_leitner",
+        "Open the Leitner card of the word",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::ReviewCorrect,
+        "review_correct",
+        "Mark the card \"correct\" and review it later",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::ReviewIncorrect,
+        "review_incorrect",
+        "Mark the card \"incorrect\" and review it sooner",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::ShowCardDefinition,
+        "show_card_definition",
+        "Show the definition of the card",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::ExportAnki,
+        "export_anki",
+        "Export the deck to an Anki TSV file",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::LeaveLeitner,
+        "leave_leitner",
+        "Switch to the default mode",
+    ),
+    #[cfg(feature = "leitner")]
+    (
+        Action::LeitnerToMinimal,
+        "leitner_to_minimal",
+        "Switch to the minimal mode",
+    ),
+    (Action::Help, "help", "Show the keys of the current mode"),
+    (
+        Action::About,
+        "about",
+        "Show the version, features and folders",
+    ),
+    (
+        Action::CommandPalette,
+        "command_palette",
+        "Find and run an action by name",
+    ),
+];
+
+impl Action {
+    /// Looks up an action by the name used in the config file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS.iter().find(|a| a.1 == name).map(|a| a.0)
+    }
+
+    pub fn description(self) -> &'static str {
+        ACTIONS.iter().find(|a| a.0 == self).map_or("", |a| a.2)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Resolution {
+    /// The keys complete a binding and are not the start of a longer one.
+    Action(Action),
+    /// The keys are the start of a longer binding. If they also complete a
+    /// shorter one, it is returned so it can run when the sequence times out.
+    Pending(Option<Action>),
+    Unbound,
+}
+
+struct Binding {
+    context: Context,
+    keys: Vec<Key>,
+    action: Action,
+}
+
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Context::*;
+        use KeyCode::*;
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+        let mut keymap = Self {
+            bindings: Vec::new(),
+        };
+        let mut bind = |context, keys: &[(KeyCode, KeyModifiers)], action| {
+            keymap.bind(
+                context,
+                keys.iter()
+                    .map(|&(code, mods)| Key::new(code, mods))
+                    .collect(),
+                action,
+            )
+        };
+        bind(Browse, &[(Char('c'), CTRL)], Action::Quit);
+        #[cfg(feature = "clipboard")]
+        bind(Browse, &[(Char('y'), CTRL)], Action::CopyDefinition);
+        #[cfg(feature = "clipboard")]
+        bind(Browse, &[(Char('y'), ALT)], Action::CopyMarkdown);
+        bind(Browse, &[(Char('m'), ALT)], Action::ToggleMinimal);
+        bind(Browse, &[(Enter, NONE)], Action::Search);
+        bind(Browse, &[(Char('a'), ALT)], Action::SearchAll);
+        bind(Browse, &[(Char('a'), CTRL)], Action::ToggleSearchAll);
+        bind(Browse, &[(Char('f'), CTRL)], Action::ToggleFilter);
+        bind(Browse, &[(Char('b'), ALT)], Action::ToggleFavorite);
+        bind(Browse, &[(Char('b'), CTRL)], Action::FavoritesOnly);
+        bind(Browse, &[(Char('x'), ALT)], Action::ExportFavorites);
+        bind(Browse, &[(F(5), NONE)], Action::Refresh);
+        bind(Browse, &[(F(1), NONE)], Action::About);
+        bind(Browse, &[(Char('p'), CTRL)], Action::CommandPalette);
+        bind(Browse, &[(Char('r'), ALT)], Action::ToggleRaw);
+        bind(Browse, &[(Char('h'), ALT)], Action::ExportHtml);
+        bind(Browse, &[(Char('t'), ALT)], Action::Pronounce);
+        bind(Browse, &[(Char('s'), ALT)], Action::CycleSearchMode);
+        bind(Browse, &[(Char('w'), ALT)], Action::ToggleWholeWord);
+        bind(Browse, &[(Char('j'), ALT)], Action::ToggleLiveSearch);
+        bind(Browse, &[(Char('z'), ALT)], Action::ToggleFold);
+        bind(Browse, &[(Tab, NONE)], Action::NextLink);
+        bind(Browse, &[(Enter, ALT)], Action::FollowLink);
+        bind(Browse, &[(Char('g'), ALT)], Action::SearchDefinitions);
+        bind(Browse, &[(Char('f'), ALT)], Action::ReverseSearch);
+        bind(Browse, &[(Char('c'), ALT)], Action::ToggleFullText);
+        bind(Browse, &[(Char('v'), ALT)], Action::Validate);
+        bind(Browse, &[(Char('e'), ALT)], Action::EditDefinition);
+        bind(Browse, &[(Char('n'), ALT)], Action::AddEntry);
+        bind(Browse, &[(Char('i'), ALT)], Action::EditEntry);
+        bind(Browse, &[(Char('d'), ALT)], Action::DeleteEntry);
+        bind(Browse, &[(Up, NONE)], Action::Up);
+        bind(Browse, &[(Down, NONE)], Action::Down);
+        bind(Browse, &[(Up, SHIFT)], Action::JumpUp);
+        bind(Browse, &[(Down, SHIFT)], Action::JumpDown);
+        bind(Browse, &[(Left, NONE)], Action::PreviousDictionary);
+        bind(Browse, &[(Right, NONE)], Action::NextDictionary);
+        bind(Browse, &[(Left, SHIFT)], Action::PreviousDictionaryWithWord);
+        bind(Browse, &[(Left, ALT)], Action::Back);
+        bind(Browse, &[(Right, ALT)], Action::Forward);
+        bind(Browse, &[(Right, SHIFT)], Action::NextDictionaryWithWord);
+        bind(Browse, &[(Char('o'), ALT)], Action::AlternateDictionary);
+        bind(Browse, &[(Char('u'), ALT)], Action::RecentDictionary);
+        bind(Browse, &[(PageUp, NONE)], Action::ScrollUp);
+        bind(Browse, &[(PageDown, NONE)], Action::ScrollDown);
+        bind(Browse, &[(Backspace, NONE)], Action::DeleteChar);
+        bind(Browse, &[(Backspace, ALT)], Action::DeleteWord);
+        bind(Browse, &[(Esc, NONE)], Action::NormalMode);
+        bind(Browse, &[(Char('q'), ALT)], Action::RecordMacro);
+        bind(Browse, &[(Char('p'), ALT)], Action::PlayMacro);
+        #[cfg(feature = "leitner")]
+        {
+            bind(Browse, &[(Char('l'), ALT)], Action::EnterLeitner);
+            bind(Browse, &[(Char('`'), NONE)], Action::AddToLeitner);
+            bind(Browse, &[(Char('k'), ALT)], Action::ShowInLeitner);
+        }
+
+        bind(Normal, &[(Char('i'), NONE)], Action::InsertMode);
+        bind(Normal, &[(Char('j'), NONE)], Action::Down);
+        bind(Normal, &[(Char('k'), NONE)], Action::Up);
+        bind(Normal, &[(Char('h'), NONE)], Action::PreviousDictionary);
+        bind(Normal, &[(Char('l'), NONE)], Action::NextDictionary);
+        bind(Normal, &[(Char('g'), NONE), (Char('g'), NONE)], Action::Top);
+        bind(Normal, &[(Char('G'), NONE)], Action::Bottom);
+        bind(Normal, &[(Char('%'), NONE)], Action::JumpPercent);
+        bind(Normal, &[(Char('d'), CTRL)], Action::JumpDown);
+        bind(Normal, &[(Char('u'), CTRL)], Action::JumpUp);
+
+        #[cfg(feature = "leitner")]
+        {
+            bind(Leitner, &[(Char('c'), CTRL)], Action::Quit);
+            bind(Leitner, &[(Char('y'), NONE)], Action::ReviewCorrect);
+            bind(Leitner, &[(Char('n'), NONE)], Action::ReviewIncorrect);
+            bind(Leitner, &[(Char('l'), ALT)], Action::LeaveLeitner);
+            bind(Leitner, &[(Char('m'), ALT)], Action::LeitnerToMinimal);
+            bind(Leitner, &[(Up, NONE)], Action::Up);
+            bind(Leitner, &[(Down, NONE)], Action::Down);
+            bind(Leitner, &[(PageUp, NONE)], Action::ScrollUp);
+            bind(Leitner, &[(PageDown, NONE)], Action::ScrollDown);
+            bind(Leitner, &[(Enter, NONE)], Action::ShowCardDefinition);
+            bind(Leitner, &[(Char(' '), NONE)], Action::ShowCardDefinition);
+            bind(Leitner, &[(Char('e'), ALT)], Action::ExportAnki);
+            bind(Leitner, &[(Char('t'), ALT)], Action::Pronounce);
+            bind(Leitner, &[(Char('q'), ALT)], Action::RecordMacro);
+            bind(Leitner, &[(Char('p'), ALT)], Action::PlayMacro);
+            bind(Leitner, &[(Char('?'), NONE)], Action::Help);
+            bind(Leitner, &[(F(1), NONE)], Action::About);
+            bind(Leitner, &[(Char('p'), CTRL)], Action::CommandPalette);
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// The default bindings with those of the `[keys.<context>]` config sections applied.
+    pub fn from_config(config: &Config) -> Self {
+        let mut keymap = Self::default();
+        for (context, action, sequences) in &config.key_bindings {
+            keymap.unbind(*context, *action);
+            for keys in sequences {
+                keymap.bind(*context, keys.clone(), *action);
+            }
+        }
+        keymap
+    }
+
+    pub fn bind(&mut self, context: Context, keys: Vec<Key>, action: Action) {
+        self.bindings
+            .retain(|b| !(b.context == context && b.keys == keys));
+        self.bindings.push(Binding {
+            context,
+            keys,
+            action,
+        });
+    }
+
+    /// Removes every binding of `action` in `context`.
+    pub fn unbind(&mut self, context: Context, action: Action) {
+        self.bindings
+            .retain(|b| !(b.context == context && b.action == action));
+    }
+
+    /// Lists the bound actions of `context` with their keys, in binding order.
+    pub fn help(&self, context: Context) -> Vec<(String, &'static str)> {
+        self.actions(&[context])
+            .into_iter()
+            .map(|(action, keys)| (keys, action.description()))
+            .collect()
+    }
+
+    /// Actions bound in any of `contexts`, in binding order, with their keys.
+    pub fn actions(&self, contexts: &[Context]) -> Vec<(Action, String)> {
+        let mut entries: Vec<(Action, Vec<String>)> = Vec::new();
+        for binding in self
+            .bindings
+            .iter()
+            .filter(|b| contexts.contains(&b.context))
+        {
+            let keys = binding
+                .keys
+                .iter()
+                .map(Key::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            match entries
+                .iter_mut()
+                .find(|(action, _)| *action == binding.action)
+            {
+                Some((_, all)) => all.push(keys),
+                None => entries.push((binding.action, vec![keys])),
+            }
+        }
+        entries
+            .into_iter()
+            .map(|(action, keys)| (action, keys.join(" / ")))
+            .collect()
+    }
+
+    /// Looks up `keys` in the given contexts, earlier contexts taking precedence.
+    pub fn resolve(&self, contexts: &[Context], keys: &[Key]) -> Resolution {
+        let mut complete = None;
+        let mut longer = false;
+        for context in contexts {
+            for binding in self.bindings.iter().filter(|b| b.context == *context) {
+                if binding.keys == keys {
+                    complete = complete.or(Some(binding.action));
+                } else if binding.keys.starts_with(keys) {
+                    longer = true;
+                }
+            }
+        }
+        match (complete, longer) {
+            (_, true) => Resolution::Pending(complete),
+            (Some(action), false) => Resolution::Action(action),
+            (None, false) => Resolution::Unbound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> Key {
+        Key::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn keys_are_parsed_with_their_modifiers() {
+        assert_eq!(Key::parse("y"), Some(key('y')));
+        assert_eq!(
+            Key::parse("Ctrl+Shift+Up"),
+            Some(Key::new(
+                KeyCode::Up,
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT
+            ))
+        );
+        assert_eq!(
+            Key::parse("alt+L"),
+            Some(Key::new(KeyCode::Char('L'), KeyModifiers::ALT))
+        );
+        assert_eq!(Key::parse("shift+g"), Some(key('g')));
+        assert_eq!(Key::parse("space"), Some(key(' ')));
+        assert_eq!(
+            Key::parse("f12"),
+            Some(Key::new(KeyCode::F(12), KeyModifiers::NONE))
+        );
+        assert_eq!(Key::parse("ctrl+"), None);
+        assert_eq!(Key::parse("nope"), None);
+        assert_eq!(Key::parse_sequence("g  g"), Some(vec![key('g'), key('g')]));
+        assert_eq!(Key::parse_sequence(" "), None);
+    }
+
+    #[test]
+    fn sequences_resolve_to_pending_actions_and_unbound() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Context::Browse, vec![key('x')], Action::Help);
+        keymap.bind(Context::Browse, vec![key('x'), key('y')], Action::About);
+        let contexts = [Context::Normal, Context::Browse];
+        assert_eq!(
+            keymap.resolve(&contexts, &[key('g')]),
+            Resolution::Pending(None)
+        );
+        assert_eq!(
+            keymap.resolve(&contexts, &[key('g'), key('g')]),
+            Resolution::Action(Action::Top)
+        );
+        assert_eq!(
+            keymap.resolve(&contexts, &[key('g'), key('x')]),
+            Resolution::Unbound
+        );
+        assert_eq!(
+            keymap.resolve(&contexts, &[key('x')]),
+            Resolution::Pending(Some(Action::Help))
+        );
+        assert_eq!(
+            keymap.resolve(&contexts, &[key('x'), key('y')]),
+            Resolution::Action(Action::About)
+        );
+        assert_eq!(
+            keymap.resolve(&[Context::Browse], &[key('g')]),
+            Resolution::Unbound
+        );
+    }
+
+    #[test]
+    fn earlier_contexts_take_precedence() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Context::Browse, vec![key('h')], Action::Help);
+        assert_eq!(
+            keymap.resolve(&[Context::Normal, Context::Browse], &[key('h')]),
+            Resolution::Action(Action::PreviousDictionary)
+        );
+        assert_eq!(
+            keymap.resolve(&[Context::Browse], &[key('h')]),
+            Resolution::Action(Action::Help)
+        );
+    }
+}
@@ -1,18 +1,76 @@
 use chrono::{Datelike, NaiveDate};
 use rusqlite::{params, Connection, Result};
-use std::path::PathBuf;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::LeitnerConfig;
 pub struct Leitner {
     conn: Connection,
     pub selected_index: usize,
+    /// `id` of each card, parallel to `word_index`.
+    ids: Vec<i64>,
     pub word_index: Vec<String>,
     pub review_due: Vec<NaiveDate>,
     pub box_level: Vec<u8>,
+    /// Failed reviews of each card since it last changed box.
+    pub attempts: Vec<u8>,
+    /// How often batched review writes are committed; `None` writes each
+    /// review right away.
+    save_interval: Option<Duration>,
+    last_flush: Instant,
+    new_cards_per_day: Option<usize>,
+    /// Box new cards start in.
+    initial_box: u8,
+    /// Days until a new card is first due.
+    initial_delay: i64,
+    /// Days until a card moved into each box is due again; a card correctly
+    /// reviewed in the last box leaves the deck.
+    intervals: Vec<u32>,
 }
 
-static INTERVALS: [u8; 5] = [1, 2, 4, 6, 10];
+/// Days until a card moved into each box is due again, unless configured.
+pub const DEFAULT_INTERVALS: [u32; 5] = [1, 2, 4, 6, 10];
+
+/// Version of the `cards` schema, stored in the database's `user_version`.
+const SCHEMA_VERSION: i32 = 2;
+
+/// Columns added to `cards` after the first schema, with the version that
+/// introduced each of them.
+static MIGRATIONS: [(i32, &str, &str); 1] = [(2, "added", "added DATE")];
+
+/// Review dates for new cards, honoring the daily limit.
+struct NewCardSchedule {
+    first: NaiveDate,
+    limit: Option<usize>,
+    counts: HashMap<NaiveDate, usize>,
+}
+
+impl NewCardSchedule {
+    /// Returns the first day with room for another new card and counts the
+    /// card in.
+    fn next_date(&mut self) -> NaiveDate {
+        let mut date = self.first;
+        if let Some(limit) = self.limit {
+            while self.counts.get(&date).copied().unwrap_or(0) >= limit {
+                date += chrono::Duration::days(1);
+            }
+        }
+        *self.counts.entry(date).or_default() += 1;
+        date
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReviewOutcome {
+    Reviewed,
+    /// The card was left untouched because it is not due until this date.
+    NotDue(NaiveDate),
+}
 
 impl Leitner {
-    pub fn new(db_path: PathBuf) -> Result<Self> {
+    pub fn new(db_path: PathBuf, config: &LeitnerConfig) -> Result<Self> {
         let conn = Connection::open(&db_path)?;
 
         conn.execute(
@@ -22,74 +80,227 @@ impl Leitner {
                 definition TEXT NOT NULL,
                 box INTEGER NOT NULL DEFAULT 1,
                 next_review DATE NOT NULL DEFAULT CURRENT_DATE,
-                attempts INTEGER NOT NULL DEFAULT 0
+                attempts INTEGER NOT NULL DEFAULT 0,
+                added DATE
             )",
             [],
         )?;
+        migrate(&conn)?;
 
-        let mut stmt = conn.prepare("SELECT word, next_review, box FROM cards")?;
+        let mut leitner = Self {
+            conn,
+            selected_index: 0,
+            ids: Vec::new(),
+            word_index: Vec::new(),
+            review_due: Vec::new(),
+            box_level: Vec::new(),
+            attempts: Vec::new(),
+            save_interval: config.save_interval.map(Duration::from_secs),
+            last_flush: Instant::now(),
+            new_cards_per_day: config.new_cards_per_day,
+            initial_box: config.initial_box,
+            initial_delay: config.initial_delay,
+            intervals: config.intervals.clone(),
+        };
+        leitner.reload()?;
+        Ok(leitner)
+    }
+
+    /// Re-reads the cards from the database into the in-memory vectors.
+    pub fn reload(&mut self) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, word, next_review, box, attempts FROM cards ORDER BY id")?;
         let mut rows = stmt.query([])?;
-        let mut word_index = Vec::new();
-        let mut review_due = Vec::new();
-        let mut box_level = Vec::new();
+        self.ids.clear();
+        self.word_index.clear();
+        self.review_due.clear();
+        self.box_level.clear();
+        self.attempts.clear();
         let parse_from_str = NaiveDate::parse_from_str;
         while let Ok(Some(row)) = rows.next() {
-            let word: String = row.get(0)?;
-            let review_date_str: String = row.get(1)?;
-            let box_n: u8 = row.get(2)?;
+            let id: i64 = row.get(0)?;
+            let word: String = row.get(1)?;
+            let review_date_str: String = row.get(2)?;
+            let box_n: u8 = row.get(3)?;
+            let attempts: u8 = row.get(4)?;
             let review_date: NaiveDate = parse_from_str(&review_date_str, "%Y-%m-%d").unwrap();
-            word_index.push(word);
-            review_due.push(review_date);
-            box_level.push(box_n);
+            self.ids.push(id);
+            self.word_index.push(word);
+            self.review_due.push(review_date);
+            self.box_level.push(box_n);
+            self.attempts.push(attempts);
         }
+        self.selected_index = min(self.selected_index, self.word_index.len().saturating_sub(1));
+        Ok(())
+    }
 
-        Ok(Self {
-            conn: Connection::open(db_path)?,
-            selected_index: 0,
-            word_index,
-            review_due,
-            box_level,
-        })
+    /// Opens a transaction for the following writes when they are batched.
+    fn begin_write(&self) -> Result<()> {
+        if self.save_interval.is_some() && self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN")?;
+        }
+        Ok(())
     }
 
-    pub fn next(&mut self) {
-        if self.word_index.is_empty() {
-            return;
+    /// Commits the batched writes, if any.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
         }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Commits the batched writes once the save interval has passed.
+    pub fn flush_if_due(&mut self) -> Result<()> {
+        match self.save_interval {
+            Some(interval) if self.last_flush.elapsed() >= interval => self.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Selects the next card due for review, looking after the current card
+    /// first and then from the start of the deck. Stays put when none is due.
+    pub fn next(&mut self) {
         let today = chrono::Local::now().date_naive();
-        while self.selected_index < self.word_index.len() - 1 {
-            let review_date = &self.review_due[self.selected_index];
-            if *review_date <= today {
-                break;
-            }
-            self.selected_index += 1;
+        let due = |i: &usize| self.review_due[*i] <= today;
+        let len = self.word_index.len();
+        if let Some(i) = (self.selected_index..len)
+            .chain(0..self.selected_index.min(len))
+            .find(due)
+        {
+            self.selected_index = i;
         }
     }
 
+    /// Number of cards due for review today or earlier.
+    pub fn due_count(&self) -> usize {
+        let today = chrono::Local::now().date_naive();
+        self.review_due
+            .iter()
+            .filter(|&&date| date <= today)
+            .count()
+    }
+
     pub fn update_index_by(&mut self, i: i32) {
+        if self.word_index.is_empty() {
+            return;
+        }
         let new_index = (self.selected_index as i32 + i).clamp(0, self.word_index.len() as i32 - 1);
         self.selected_index = new_index as usize;
     }
 
-    pub fn add(&mut self, word: &str, definition: &str) -> Result<()> {
-        let review_date = chrono::Local::now().date_naive() + chrono::Duration::days(1.into());
+    /// Adds a card for `word`. Returns `false`, leaving the deck unchanged,
+    /// when the word already has one.
+    pub fn add(&mut self, word: &str, definition: &str) -> Result<bool> {
+        if self.word_index.iter().any(|w| w == word) {
+            return Ok(false);
+        }
+        let review_date = self.new_card_schedule()?.next_date();
         self.conn.execute(
-            "INSERT INTO cards (word, definition, box, next_review) 
-             VALUES (?1, ?2, 1, ?3)",
-            params![word, definition, review_date.format("%Y-%m-%d").to_string()],
+            "INSERT INTO cards (word, definition, box, next_review, added)
+             VALUES (?1, ?2, ?3, ?4, CURRENT_DATE)",
+            params![
+                word,
+                definition,
+                self.initial_box,
+                review_date.format("%Y-%m-%d").to_string()
+            ],
         )?;
+        self.ids.push(self.conn.last_insert_rowid());
         self.word_index.push(word.to_string());
         self.review_due.push(review_date);
-        self.box_level.push(1);
-        Ok(())
+        self.box_level.push(self.initial_box);
+        self.attempts.push(0);
+        Ok(true)
+    }
+
+    /// Adds the cards of an Anki TSV export (front and back columns) as new
+    /// cards, skipping words that are already in the deck. Returns the number
+    /// of cards added. `progress` is called periodically with the number of
+    /// records processed and the total.
+    pub fn import_anki(
+        &mut self,
+        path: &Path,
+        mut progress: impl FnMut(usize, usize),
+    ) -> std::io::Result<usize> {
+        let content = std::fs::read_to_string(path)?;
+        let to_io = |e: rusqlite::Error| std::io::Error::other(e);
+        let mut schedule = self.new_card_schedule().map_err(to_io)?;
+        let mut known: HashSet<String> = self.word_index.iter().cloned().collect();
+        self.flush().map_err(to_io)?;
+        let tx = self.conn.transaction().map_err(to_io)?;
+        let mut count = 0;
+        let records = parse_tsv(&content);
+        let total = records.len();
+        for (i, record) in records.iter().enumerate() {
+            if i % 250 == 0 {
+                progress(i, total);
+            }
+            let [front, back, ..] = record.as_slice() else {
+                continue;
+            };
+            let word = from_anki_field(front);
+            if word.is_empty() || !known.insert(word.clone()) {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO cards (word, definition, box, next_review, added)
+                 VALUES (?1, ?2, ?3, ?4, CURRENT_DATE)",
+                params![
+                    word,
+                    from_anki_field(back),
+                    self.initial_box,
+                    schedule.next_date().format("%Y-%m-%d").to_string()
+                ],
+            )
+            .map_err(to_io)?;
+            count += 1;
+        }
+        tx.commit().map_err(to_io)?;
+        progress(total, total);
+        self.reload().map_err(to_io)?;
+        Ok(count)
+    }
+
+    /// Counts the new cards already scheduled from their first due day on, so
+    /// that the next ones can be spread over the days under the daily limit.
+    fn new_card_schedule(&self) -> Result<NewCardSchedule> {
+        let first = chrono::Local::now().date_naive() + chrono::Duration::days(self.initial_delay);
+        let mut counts = HashMap::new();
+        if self.new_cards_per_day.is_some() {
+            let mut stmt = self.conn.prepare(
+                "SELECT next_review, COUNT(*) FROM cards
+                 WHERE box = ?2 AND attempts = 0 AND next_review >= ?1
+                 GROUP BY next_review",
+            )?;
+            let rows = stmt.query_map(
+                params![first.format("%Y-%m-%d").to_string(), self.initial_box],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?)),
+            )?;
+            for row in rows {
+                let (date, count) = row?;
+                if let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+                    counts.insert(date, count);
+                }
+            }
+        }
+        Ok(NewCardSchedule {
+            first,
+            limit: self.new_cards_per_day,
+            counts,
+        })
     }
 
     pub fn get_definition(&mut self, i: usize) -> String {
         let sql = "SELECT definition FROM cards WHERE ROWID = :query";
-        let wild_card_query = (i + 1).to_string();
+        let Some(&id) = self.ids.get(i) else {
+            return "Not found!".to_string();
+        };
         let mut stmt = self.conn.prepare(sql).unwrap();
         let mut rows = stmt
-            .query_map([(wild_card_query)], |row| {
+            .query_map([id], |row| {
                 let def: String = row.get(0)?;
                 Ok(def)
             })
@@ -102,40 +313,88 @@ impl Leitner {
             "Not found!".to_string()
         }
     }
-    pub fn review(&mut self, success: bool) -> Result<()> {
+    /// Writes all cards to `path` as an Anki importable TSV file, optionally
+    /// tagging each card with its box and due date. Returns the number of cards.
+    pub fn export_anki(&self, path: &Path, schedule_tags: bool) -> std::io::Result<usize> {
+        let to_io = |e: rusqlite::Error| std::io::Error::other(e);
+        let mut stmt = self
+            .conn
+            .prepare("SELECT word, definition, box, next_review FROM cards ORDER BY id")
+            .map_err(to_io)?;
+        let mut rows = stmt.query([]).map_err(to_io)?;
+        let mut out = String::from("#separator:tab\n#html:true\n");
+        if schedule_tags {
+            out.push_str("#tags column:3\n");
+        }
+        let mut count = 0;
+        while let Some(row) = rows.next().map_err(to_io)? {
+            let word: String = row.get(0).map_err(to_io)?;
+            let definition: String = row.get(1).map_err(to_io)?;
+            out.push_str(&anki_field(&word));
+            out.push('\t');
+            out.push_str(&anki_field(&definition));
+            if schedule_tags {
+                let box_n: u8 = row.get(2).map_err(to_io)?;
+                let due: String = row.get(3).map_err(to_io)?;
+                out.push_str(&format!(
+                    "\tdicrs leitner::box{} leitner::due::{}",
+                    box_n, due
+                ));
+            }
+            out.push('\n');
+            count += 1;
+        }
+        std::fs::write(path, out)?;
+        Ok(count)
+    }
+
+    /// Number of boxes.
+    pub fn box_count(&self) -> u8 {
+        self.intervals.len() as u8
+    }
+
+    pub fn review(&mut self, success: bool) -> Result<ReviewOutcome> {
         if self.selected_index < self.word_index.len() {
             let today = chrono::Local::now().date_naive();
             let (review_date, current_box, attempts): (String, u8, u8) = self.conn.query_row(
                 "SELECT next_review, box, attempts FROM cards WHERE ROWID = ?1",
-                params![self.selected_index + 1],
+                params![self.ids[self.selected_index]],
                 |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
             )?;
             let mut review_date =
                 chrono::NaiveDate::parse_from_str(&review_date, "%Y-%m-%d").unwrap();
             if review_date > today {
-                return Ok(());
+                return Ok(ReviewOutcome::NotDue(review_date));
             }
+            // Cards may be in a box that no longer exists after fewer
+            // intervals were configured. Boxes are counted in `usize`, as
+            // passing the last of 255 boxes leaves the range of `u8`.
+            let current_box = usize::from(current_box.min(self.box_count()));
             let (new_box, new_attempts) = if success {
                 (current_box + 1, 0)
-            } else if attempts + 1 >= 2 && current_box > 1 {
+            } else if attempts.saturating_add(1) >= 2 && current_box > 1 {
                 (current_box - 1, 0)
             } else {
-                (current_box, attempts + 1)
+                (current_box, attempts.saturating_add(1))
             };
 
-            if new_box == 6 {
+            self.begin_write()?;
+            if new_box > self.intervals.len() {
                 self.conn.execute(
                     "DELETE FROM cards WHERE ROWID = ?1",
-                    params![self.selected_index + 1],
+                    params![self.ids[self.selected_index]],
                 )?;
+                self.ids.remove(self.selected_index);
                 self.word_index.remove(self.selected_index);
                 self.review_due.remove(self.selected_index);
                 self.box_level.remove(self.selected_index);
+                self.attempts.remove(self.selected_index);
                 if self.selected_index >= self.word_index.len() {
-                    self.selected_index = self.word_index.len() - 1;
+                    self.selected_index = self.word_index.len().saturating_sub(1);
                 }
             } else {
-                let new_days = INTERVALS[(new_box - 1) as usize];
+                let new_days = self.intervals[new_box - 1];
+                let new_box = new_box as u8;
                 review_date = today + chrono::Duration::days(new_days.into());
                 self.conn.execute(
                     "UPDATE cards 
@@ -145,33 +404,145 @@ impl Leitner {
                         new_box,
                         review_date.format("%Y-%m-%d").to_string(),
                         new_attempts,
-                        self.selected_index + 1
+                        self.ids[self.selected_index]
                     ],
                 )?;
                 self.review_due[self.selected_index] = review_date;
                 self.box_level[self.selected_index] = new_box;
+                self.attempts[self.selected_index] = new_attempts;
             }
-            Ok(())
+            Ok(ReviewOutcome::Reviewed)
         } else {
             Err(rusqlite::Error::InvalidQuery)
         }
     }
 }
 
-pub fn get_box_symbol(box_num: u8) -> String {
-    match box_num {
-        1 => "★☆☆☆☆".to_string(),
-        2 => "★★☆☆☆".to_string(),
-        3 => "★★★☆☆".to_string(),
-        4 => "★★★★☆".to_string(),
-        5 => "★★★★★".to_string(),
-        _ => "☆☆☆☆☆".to_string(),
+impl Drop for Leitner {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Brings a deck created by an older version up to `SCHEMA_VERSION` by adding
+/// the columns it lacks. Decks from a newer version are left as they are.
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    let columns: HashSet<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('cards')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    for (_, name, definition) in MIGRATIONS.iter().filter(|(v, _, _)| *v > version) {
+        if !columns.contains(*name) {
+            conn.execute(&format!("ALTER TABLE cards ADD COLUMN {}", definition), [])?;
+        }
+    }
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+/// Splits tab separated records, honoring double quoted fields that may
+/// contain tabs, newlines and doubled quotes. Leading `#` header lines are skipped.
+fn parse_tsv(content: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = content.chars().peekable();
+    let mut at_line_start = true;
+    let mut in_header = true;
+    while let Some(c) = chars.next() {
+        if at_line_start && in_header && c == '#' {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        in_header &= !at_line_start || c == '#';
+        at_line_start = false;
+        match c {
+            '"' if field.is_empty() => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        '"' => break,
+                        c => field.push(c),
+                    }
+                }
+            }
+            '\t' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                at_line_start = true;
+            }
+            c => field.push(c),
+        }
     }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// Reverses `anki_field`: line breaks become newlines and entities are decoded.
+fn from_anki_field(field: &str) -> String {
+    field
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Formats a field for Anki's tab separated import with HTML enabled.
+fn anki_field(field: &str) -> String {
+    field
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace("\r\n", "\n")
+        .replace(['\r', '\n'], "<br>")
+        .replace('\t', " ")
 }
 
-pub fn get_relative_date(date: NaiveDate) -> String {
-    let today = chrono::Local::now().date_naive();
+/// One star per box, filled up to `box_num`; all empty when `box_num` is not
+/// one of the `box_count` boxes.
+pub fn get_box_symbol(box_num: u8, box_count: u8) -> String {
+    let filled = match box_num {
+        1.. if box_num <= box_count => box_num,
+        _ => 0,
+    };
+    "★".repeat(filled.into()) + &"☆".repeat((box_count - filled).into())
+}
+
+/// Marks a card that failed its recent reviews, e.g. "✗2"; empty when it has
+/// not failed since it last changed box.
+pub fn get_attempts_symbol(attempts: u8) -> String {
+    match attempts {
+        0 => String::new(),
+        n => format!("\u{2717}{}", n),
+    }
+}
+
+/// Describes how far `date` is from `today`: by weekday within this week,
+/// then in days, weeks and months as `config` sets.
+pub fn get_relative_date(date: NaiveDate, today: NaiveDate, config: &LeitnerConfig) -> String {
     let tomorrow = today + chrono::Duration::days(1);
+    let days = date.signed_duration_since(today).num_days();
 
     if date <= today {
         return "Today".to_string();
@@ -179,10 +550,221 @@ pub fn get_relative_date(date: NaiveDate) -> String {
         return "Tomorrow".to_string();
     } else if date.iso_week() == today.iso_week() {
         return format!("{}", date.weekday());
-    } else if date.signed_duration_since(today).num_days() <= 7 {
+    } else if days <= 7 {
         return "Next week".to_string();
-    } else if date.signed_duration_since(today).num_days() <= 10 {
-        return format!("In {} days", date.signed_duration_since(today).num_days());
+    } else if days <= config.relative_days {
+        return format!("In {}", plural(days, "day"));
+    } else if days <= config.relative_weeks * 7 {
+        return format!("In {}", plural((days as f64 / 7.0).round() as i64, "week"));
+    }
+    let months = max(1, (days as f64 / 30.0).round() as i64);
+    format!("In {}", plural(months, "month"))
+}
+
+fn plural(count: i64, unit: &str) -> String {
+    match count {
+        1 => format!("1 {}", unit),
+        _ => format!("{} {}s", count, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deck() -> Leitner {
+        Leitner::new(PathBuf::from(":memory:"), &LeitnerConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn relative_dates_follow_the_configured_limits() {
+        // A Wednesday.
+        let today = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+        let config = LeitnerConfig::default();
+        let relative = |days: i64, config: &LeitnerConfig| {
+            get_relative_date(today + chrono::Duration::days(days), today, config)
+        };
+        assert_eq!(relative(-3, &config), "Today");
+        assert_eq!(relative(0, &config), "Today");
+        assert_eq!(relative(1, &config), "Tomorrow");
+        assert_eq!(relative(2, &config), "Fri");
+        assert_eq!(relative(5, &config), "Next week");
+        assert_eq!(relative(7, &config), "Next week");
+        assert_eq!(relative(8, &config), "In 8 days");
+        assert_eq!(relative(10, &config), "In 10 days");
+        assert_eq!(relative(11, &config), "In 2 weeks");
+        assert_eq!(relative(56, &config), "In 8 weeks");
+        assert_eq!(relative(57, &config), "In 2 months");
+        let config = LeitnerConfig {
+            relative_days: 8,
+            relative_weeks: 2,
+            ..LeitnerConfig::default()
+        };
+        assert_eq!(relative(9, &config), "In 1 week");
+        assert_eq!(relative(14, &config), "In 2 weeks");
+        assert_eq!(relative(15, &config), "In 1 month");
+        assert_eq!(relative(365, &config), "In 12 months");
+    }
+
+    #[test]
+    fn reviewing_a_card_that_is_not_due_leaves_it_untouched() {
+        let mut leitner = deck();
+        leitner.add("word", "definition").unwrap();
+        let due = leitner.review_due[0];
+        assert_eq!(leitner.review(true), Ok(ReviewOutcome::NotDue(due)));
+        assert_eq!(leitner.box_level[0], 1);
+        assert_eq!(leitner.review_due[0], due);
+    }
+
+    #[test]
+    fn reviewing_a_due_card_moves_it_up_a_box() {
+        let mut leitner = deck();
+        leitner.add("word", "definition").unwrap();
+        leitner
+            .conn
+            .execute("UPDATE cards SET next_review = '2000-01-01'", [])
+            .unwrap();
+        leitner.reload().unwrap();
+        assert_eq!(leitner.review(true), Ok(ReviewOutcome::Reviewed));
+        assert_eq!(leitner.box_level[0], 2);
+    }
+
+    #[test]
+    fn new_cards_are_spread_over_days_under_the_limit() {
+        let config = LeitnerConfig {
+            new_cards_per_day: Some(2),
+            ..LeitnerConfig::default()
+        };
+        let mut leitner = Leitner::new(PathBuf::from(":memory:"), &config).unwrap();
+        for word in ["a", "b", "c", "d", "e"] {
+            leitner.add(word, "definition").unwrap();
+        }
+        let first = leitner.review_due[0];
+        let days: Vec<i64> = leitner
+            .review_due
+            .iter()
+            .map(|date| (*date - first).num_days())
+            .collect();
+        assert_eq!(days, [0, 0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn words_already_in_the_deck_are_not_added_again() {
+        let mut leitner = deck();
+        assert_eq!(leitner.add("word", "definition"), Ok(true));
+        assert_eq!(leitner.add("word", "other definition"), Ok(false));
+        assert_eq!(leitner.word_index, ["word"]);
+    }
+
+    #[test]
+    fn new_cards_start_in_the_configured_box() {
+        let config = LeitnerConfig {
+            initial_box: 3,
+            initial_delay: 0,
+            ..LeitnerConfig::default()
+        };
+        let mut leitner = Leitner::new(PathBuf::from(":memory:"), &config).unwrap();
+        leitner.add("word", "definition").unwrap();
+        assert_eq!(leitner.box_level[0], 3);
+        assert_eq!(leitner.review_due[0], chrono::Local::now().date_naive());
+        assert_eq!(leitner.review(true), Ok(ReviewOutcome::Reviewed));
+        assert_eq!(leitner.box_level[0], 4);
+    }
+
+    #[test]
+    fn the_number_of_boxes_follows_the_configured_intervals() {
+        let config = LeitnerConfig {
+            intervals: vec![3, 30],
+            ..LeitnerConfig::default()
+        };
+        let mut leitner = Leitner::new(PathBuf::from(":memory:"), &config).unwrap();
+        leitner.add("word", "definition").unwrap();
+        let due_now = |leitner: &mut Leitner| {
+            leitner
+                .conn
+                .execute("UPDATE cards SET next_review = '2000-01-01'", [])
+                .unwrap();
+            leitner.reload().unwrap();
+        };
+        due_now(&mut leitner);
+        leitner.review(true).unwrap();
+        let today = chrono::Local::now().date_naive();
+        assert_eq!(leitner.box_level[0], 2);
+        assert_eq!((leitner.review_due[0] - today).num_days(), 30);
+        due_now(&mut leitner);
+        leitner.review(true).unwrap();
+        assert!(leitner.word_index.is_empty());
+        assert_eq!(get_box_symbol(2, 3), "★★☆");
+        assert_eq!(get_box_symbol(4, 3), "☆☆☆");
+    }
+
+    #[test]
+    fn passing_the_last_of_255_boxes_retires_the_card() {
+        let config = LeitnerConfig {
+            intervals: vec![1; 255],
+            initial_box: 255,
+            initial_delay: 0,
+            ..LeitnerConfig::default()
+        };
+        let mut leitner = Leitner::new(PathBuf::from(":memory:"), &config).unwrap();
+        leitner.add("word", "definition").unwrap();
+        assert_eq!(leitner.box_level[0], 255);
+        assert_eq!(leitner.review(true), Ok(ReviewOutcome::Reviewed));
+        assert!(leitner.word_index.is_empty());
+    }
+
+    #[test]
+    fn batched_reviews_are_committed_on_flush() {
+        let config = LeitnerConfig {
+            save_interval: Some(60),
+            ..LeitnerConfig::default()
+        };
+        let mut leitner = Leitner::new(PathBuf::from(":memory:"), &config).unwrap();
+        leitner.add("word", "definition").unwrap();
+        leitner
+            .conn
+            .execute("UPDATE cards SET next_review = '2000-01-01'", [])
+            .unwrap();
+        leitner.review(true).unwrap();
+        assert!(!leitner.conn.is_autocommit());
+        leitner.flush().unwrap();
+        assert!(leitner.conn.is_autocommit());
+        let box_n: u8 = leitner
+            .conn
+            .query_row("SELECT box FROM cards", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(box_n, 2);
+    }
+
+    #[test]
+    fn migrating_a_version_1_deck_adds_the_new_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE cards (
+                id INTEGER PRIMARY KEY,
+                word TEXT NOT NULL,
+                definition TEXT NOT NULL,
+                box INTEGER NOT NULL DEFAULT 1,
+                next_review DATE NOT NULL DEFAULT CURRENT_DATE,
+                attempts INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO cards (word, definition) VALUES ('word', 'definition')",
+            [],
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+        let version: i32 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        let added: Option<String> = conn
+            .query_row("SELECT added FROM cards", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(added, None);
+        migrate(&conn).unwrap();
     }
-    "".to_string()
 }
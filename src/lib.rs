@@ -0,0 +1,19 @@
+//! The dictionary engine behind the dicrs terminal application: opening
+//! dictionaries, looking words up, and the Leitner deck, usable without the
+//! terminal interface.
+
+pub mod ansi;
+pub mod config;
+pub mod dictionary;
+pub mod favorites;
+pub mod fulltext;
+pub mod html;
+pub mod keymap;
+#[cfg(feature = "leitner")]
+pub mod leitner;
+pub mod schema;
+pub mod search;
+pub mod state;
+pub mod tools;
+
+pub use dictionary::{Dictionary, Entry};
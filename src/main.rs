@@ -1,29 +1,66 @@
 use std::cmp::{max, min};
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{error::Error, io, io::stdout};
-use std::{fs, path};
 
 use color_eyre::config::HookBuilder;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     terminal::{
         disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
     },
     ExecutableCommand,
 };
-use ratatui::{prelude::*, widgets::*};
+use ratatui::{buffer::Buffer, prelude::*, widgets::*};
 
+use regex::Regex;
 use rusqlite::Connection;
 
 #[cfg(feature = "clipboard")]
 use clipboard::{ClipboardContext, ClipboardProvider};
 
-static DICEXTENSION: &str = ".db";
+static STATE_FILE: &str = "state.toml";
+/// Below this size only a message asking to resize the terminal is drawn.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+const TICK_RATE: Duration = Duration::from_millis(100);
+/// How long to wait for the next key of a multi-key sequence.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Shown for entries whose definition is NULL or empty.
+const NO_DEFINITION: &str = "(no definition)";
+/// Headwords read from the dictionary at a time while its index loads.
+const INDEX_CHUNK: usize = 20_000;
+/// How long a status message stays under the definition.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+/// Most entries listed by a reverse search.
+const REVERSE_SEARCH_LIMIT: usize = 200;
+/// Most entries listed by a full-text search.
+const FULL_TEXT_LIMIT: usize = 200;
+/// Most headwords suggested when a search finds nothing.
+const SUGGESTIONS: usize = 5;
+/// Most visited entries remembered for going back and forward.
+const HISTORY_LIMIT: usize = 100;
+/// How many recently used dictionaries are cycled through.
+const RECENT_DICTIONARIES: usize = 4;
+
+mod cli;
+use dicrs::config::{self, Config, DictionaryConfig};
+use dicrs::favorites::{self, Favorites};
+use dicrs::fulltext::{self, FullText};
+use dicrs::keymap::{Action, Context, Key, Keymap, Resolution};
+use dicrs::search::{self, SearchMode};
+use dicrs::state::State;
+use dicrs::{ansi, dictionary, html, tools, Dictionary, Entry};
 
 #[cfg(feature = "leitner")]
-mod leitner;
-#[cfg(feature = "leitner")]
-use leitner::Leitner;
+use dicrs::leitner::{self, Leitner};
 
 #[derive(PartialEq)]
 enum Mode {
@@ -32,62 +69,391 @@ enum Mode {
     #[cfg(feature = "leitner")]
     Leitner,
 }
+
+/// What the index is filtered by while filtering is on.
+#[derive(Clone, Copy, PartialEq)]
+enum FilterKind {
+    /// Words matching the input in the current search mode.
+    Input,
+    /// The favorites of the dictionary.
+    Favorites,
+    /// The first entries whose definition contains the input, ignoring case.
+    Reverse,
+    /// Entries whose definition matches `App::definition_pattern`.
+    Definitions,
+    /// Entries whose definition has the words of the input, the most
+    /// relevant first.
+    FullText,
+}
+
 struct App {
     input: String,
     definition: String,
     selected_index: usize,
     dictionary_index: usize,
+    /// The dictionary used before the current one, for switching back and forth.
+    previous_dictionary_index: usize,
+    /// Recently used dictionaries, the current one first.
+    recent_dictionaries: VecDeque<usize>,
+    /// Visited entries as dictionary and index positions, and the position
+    /// in this history of the current one.
+    history: Vec<(usize, usize)>,
+    history_cursor: usize,
     dicpath: PathBuf,
+    datapath: PathBuf,
     database_path: PathBuf,
-    conn: Connection,
+    dictionary: Dictionary,
     word_index: Vec<String>,
+    /// `word_index` normalized by `search::fold`, used for matching only.
+    word_index_folded: Vec<String>,
+    /// `search::anagram_key` of each `word_index` entry, computed on the
+    /// first anagram search.
+    anagram_keys: Vec<String>,
+    /// ROWID of each `word_index` entry, sorted ascending.
+    rowids: Vec<i64>,
+    /// Number of entries in the dictionary. The index is read in chunks
+    /// between frames until `word_index` holds them all.
+    index_total: usize,
+    /// Row ids of the entries of each headword that has more than one.
+    duplicates: HashMap<String, Vec<i64>>,
+    /// Positions in `word_index` kept while filtering is on.
+    filter: Option<Vec<usize>>,
+    filter_cursor: usize,
+    filter_kind: FilterKind,
+    /// The selected entry is shown in full although it is longer than
+    /// `fold_lines`.
+    expanded: bool,
+    /// Related headwords of the selected entry, and the one selected with
+    /// Tab.
+    see_also: Vec<String>,
+    link: Option<usize>,
+    /// An input that found nothing, the closest headwords shown instead, and
+    /// the one selected with Tab.
+    suggestions: Option<(String, Vec<String>)>,
+    suggestion: usize,
+    /// Look the input up as it is typed, and the input last looked up so.
+    live_search: bool,
+    live_query: String,
+    /// When the input was last typed into, until the index and the live
+    /// search have followed it.
+    input_edited: Option<Instant>,
+    /// Pattern searched for in the definitions, whose matches are highlighted.
+    definition_pattern: Option<Regex>,
+    /// The input searches the definitions instead of the headwords.
+    full_text_mode: bool,
+    /// Full-text index of the current dictionary, opened on first use.
+    full_text: Option<FullText>,
+    /// Enter searches all dictionaries instead of the current one.
+    search_all_mode: bool,
+    favorites: Favorites,
+    /// Whether the selected word is one of the favorites.
+    is_favorite: bool,
+    /// The input whose stem led to the shown headword, which it is not.
+    stemmed_from: Option<String>,
     databases: Vec<String>,
+    /// File of each dictionary, parallel to `databases`.
+    database_paths: Vec<PathBuf>,
+    /// Showing a dictionary file given on the command line instead of the
+    /// scanned ones; it is not remembered as the last used dictionary.
+    standalone: bool,
     #[cfg(feature = "leitner")]
     leitner: Leitner,
     mode: Mode,
+    overlay: Option<Overlay>,
+    /// Keys captured while a macro is being recorded.
+    recording: Option<Vec<KeyEvent>>,
+    macro_keys: Vec<KeyEvent>,
+    keymap: Keymap,
+    /// In normal mode plain letters run commands instead of being typed.
+    normal_mode: bool,
+    /// Keys of a multi-key sequence typed so far.
+    pending_keys: Vec<Key>,
+    pending_since: Instant,
+    /// Numeric prefix typed in normal mode, e.g. the 10 in `10j`.
+    count: Option<usize>,
+    /// Short message shown under the definition until `STATUS_TIMEOUT` passes.
+    status: Option<(String, Instant)>,
+    search_mode: SearchMode,
+    /// Contains searches only match the input as a whole word.
+    whole_word: bool,
+    /// Show definitions exactly as stored, with control characters escaped.
+    show_raw: bool,
+    /// Running text-to-speech command, checked for failure between key presses.
+    speech: Option<std::process::Child>,
+    /// Set by the edit action; the run loop then suspends the TUI for the editor.
+    edit_requested: bool,
     scroll: u16,
+    config: Config,
     #[cfg(feature = "clipboard")]
     clipboard: Option<ClipboardContext>,
+    /// Where the definition was last drawn, for mapping mouse clicks to words.
+    definition_area: Rect,
+    /// Copy of the last drawn frame, kept when the mouse is captured.
+    screen: Buffer,
+    /// Set by the signal handlers to leave the main loop and exit cleanly.
+    terminate: Arc<AtomicBool>,
 }
 
-#[derive(Default)]
-struct DicEntry {
-    index: usize,
+struct SearchHit {
+    dictionary_index: usize,
+    rowid: i64,
     word: String,
-    definition: String,
+}
+
+enum Overlay {
+    Results {
+        hits: Vec<SearchHit>,
+        cursor: usize,
+    },
+    NewEntry {
+        step: EntryStep,
+        word: String,
+        definition: String,
+        /// ROWID of the entry whose definition is edited, rather than adding
+        /// one.
+        rowid: Option<i64>,
+    },
+    ConfirmDelete {
+        rowid: i64,
+        word: String,
+    },
+    /// Offer to add a word that has no Leitner card yet.
+    #[cfg(feature = "leitner")]
+    ConfirmAddToLeitner {
+        word: String,
+    },
+    /// Actions of the current mode whose description matches `input`.
+    Palette {
+        input: String,
+        cursor: usize,
+    },
+    /// Prompt for a percentage to jump to in the index.
+    JumpPercent {
+        input: String,
+    },
+    /// Keys of a context, or other labeled lines, closed by any key.
+    Help {
+        title: &'static str,
+        entries: Vec<(String, String)>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EntryStep {
+    Word,
+    Definition,
+    /// The word already exists; waiting for y/n.
+    Overwrite,
+}
+
+/// File of the full-text index of the dictionary at `dictionary`, in the
+/// `fulltext` folder of `datapath`, which is created when missing. The name
+/// has a hash of the canonical path, as dictionaries in different folders
+/// may share a file name; FNV-1a keeps it the same across builds.
+fn full_text_path(datapath: &Path, dictionary: &Path) -> io::Result<PathBuf> {
+    let dir = datapath.join("fulltext");
+    fs::create_dir_all(&dir)?;
+    let canonical = fs::canonicalize(dictionary).unwrap_or_else(|_| dictionary.to_path_buf());
+    let hash = canonical
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    let name = dictionary.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(dir.join(format!("{}-{:016x}.sqlite", name, hash)))
+}
+
+/// The configuration file: in the platform's config folder, or
+/// `~/.config/dicrs` where earlier versions read it on every platform.
+fn config_file() -> Option<PathBuf> {
+    let legacy = dirs::home_dir().map(|home| home.join(".config/dicrs/config.toml"));
+    legacy
+        .filter(|file| file.is_file())
+        .or_else(|| dirs::config_dir().map(|dir| dir.join("dicrs/config.toml")))
+}
+
+/// Folder of the dictionaries, the Leitner deck and the state: the
+/// platform's data folder, or `~/.local/share/dicrs` where earlier versions
+/// kept it on every platform.
+fn data_dir() -> Option<PathBuf> {
+    let legacy = dirs::home_dir().map(|home| home.join(".local/share/dicrs"));
+    legacy
+        .filter(|dir| dir.is_dir())
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("dicrs")))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let home_dir = std::env::var("HOME").expect("HOME environment variable not set");
-    let dicpath: PathBuf = path::Path::new(&home_dir).join(".local/share/dicrs/dictionaries/");
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("dicrs: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let datapath =
+        data_dir().ok_or_else(|| Box::<dyn Error>::from("Could not locate the data directory."))?;
+    let dicpath: PathBuf = datapath.join("dictionaries/");
     if !dicpath.exists() {
-        fs::create_dir_all(&dicpath)?;
+        fs::create_dir_all(&dicpath).map_err(|e| {
+            Box::<dyn Error>::from(format!("Could not create '{}': {}", dicpath.display(), e))
+        })?;
+    }
+    let config_path = config_file()
+        .ok_or_else(|| Box::<dyn Error>::from("Could not locate the config directory."))?;
+    let mut config = Config::load(&config_path).map_err(|e| {
+        Box::<dyn Error>::from(format!("Invalid config '{}': {}", config_path.display(), e))
+    })?;
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        config.theme.make_monochrome();
+    }
+    let dirs: Vec<PathBuf> = std::iter::once(dicpath.clone())
+        .chain(config.directories.iter().cloned())
+        .collect();
+    let mut file = None;
+    let mut lookup = None;
+    match command {
+        cli::Command::Tui => {}
+        cli::Command::Lookup {
+            dictionary: name,
+            word,
+        } => {
+            let (names, _) = dictionary::scan(&dicpath, &config);
+            let Some(index) = names.iter().position(|n| *n == name) else {
+                eprintln!(
+                    "dicrs: unknown dictionary '{}'\n\nAvailable dictionaries:\n  {}",
+                    name,
+                    names.join("\n  ")
+                );
+                std::process::exit(1);
+            };
+            lookup = Some((index, word));
+        }
+        cli::Command::List => {
+            for name in dictionary::scan(&dicpath, &config).0 {
+                println!("{}", name);
+            }
+            return Ok(());
+        }
+        cli::Command::Open { path } => {
+            if !path.is_file() {
+                return Err(format!("'{}' does not exist", path.display()).into());
+            }
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let dictionary = Dictionary::open(&path, config.dictionary(&name));
+            if let Err(e) = dictionary.and_then(|dictionary| dictionary.count()) {
+                return Err(format!("Could not open '{}': {}", path.display(), e).into());
+            }
+            file = Some(path);
+        }
+        cli::Command::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        cli::Command::Diff { a, b, output } => {
+            let a = tools::resolve_dictionary(&a, &dirs, &config.extensions);
+            let b = tools::resolve_dictionary(&b, &dirs, &config.extensions);
+            return tools::diff(&a, &b, output.as_deref(), &config);
+        }
+        cli::Command::Index { dictionary } => {
+            let path = tools::resolve_dictionary(&dictionary, &dirs, &config.extensions);
+            if !path.is_file() {
+                return Err(format!("'{}' does not exist", path.display()).into());
+            }
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let dictionary = Dictionary::open(&path, config.dictionary(&name))?;
+            let mut full_text = FullText::open(&full_text_path(&datapath, &path)?)?;
+            let count = full_text.build(&dictionary, &fulltext::stamp(&path))?;
+            println!("Indexed {} definitions of {}", count, path.display());
+            return Ok(());
+        }
+        cli::Command::Validate { dictionary } => {
+            let path = tools::resolve_dictionary(&dictionary, &dirs, &config.extensions);
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let report = tools::validate(&path, config.dictionary(&name))?;
+            print!("{}", report);
+            std::process::exit(if report.problems.is_empty() { 0 } else { 1 });
+        }
+        #[cfg(feature = "leitner")]
+        cli::Command::ExportAnki { path, tags } => {
+            let leitner = Leitner::new(datapath.join("leitner.sqlite"), &config.leitner)?;
+            let count = leitner.export_anki(&path, tags)?;
+            println!("Exported {} cards to {}", count, path.display());
+            return Ok(());
+        }
+        #[cfg(feature = "leitner")]
+        cli::Command::ImportAnki { path } => {
+            let mut leitner = Leitner::new(datapath.join("leitner.sqlite"), &config.leitner)?;
+            let count = leitner.import_anki(&path, |done, total| {
+                let percent = (done * 100).checked_div(total).unwrap_or(100);
+                eprint!("\rImporting... {}/{} ({}%)", done, total, percent);
+                if done == total {
+                    eprintln!();
+                }
+            })?;
+            println!("Imported {} new cards from {}", count, path.display());
+            return Ok(());
+        }
     }
+    let mouse = config.ui.mouse;
+    let auto_compact = config.ui.auto_compact;
+    let mut app = App::default(config, datapath, dicpath.clone(), Mode::Default)?;
     init_error_hooks()?;
     let terminal = init_terminal()?;
+    if mouse {
+        stdout().execute(EnableMouseCapture)?;
+    }
     let size = terminal.size().unwrap();
-    let starting_mode = if size.height > 16 && size.width > 54 {
-        Mode::Default
-    } else {
-        Mode::Minimal
-    };
+    if auto_compact && (size.height <= 16 || size.width <= 54) {
+        app.mode = Mode::Minimal;
+    }
     crossterm::execute!(io::stdout(), SetTitle("dic.rs")).unwrap();
-    let mut app = App::default(dicpath.clone(), starting_mode);
-    if app.databases.is_empty() {
-        restore_terminal()?;
-        return Err(Box::<dyn Error>::from(
-            "No databases found in '.local/share/dicrs/dictionaries/'.",
-        ));
+    let state = State::load(&app.datapath.join(STATE_FILE));
+    app.search_mode = state.search_mode.unwrap_or_default();
+    if let Some(path) = file {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        app.databases = vec![name.into_owned()];
+        app.database_paths = vec![path];
+        app.standalone = true;
+        app.create(app.database_file(0));
+    } else if !app.databases.is_empty() {
+        app.dictionary_index = match lookup {
+            Some((index, _)) => index,
+            None => state
+                .dictionary
+                .and_then(|name| app.databases.iter().position(|db| *db == name))
+                .unwrap_or(0),
+        };
+        app.remember_dictionary();
+        app.create(app.database_file(app.dictionary_index));
+        if let Some((_, Some(word))) = lookup {
+            app.finish_loading();
+            app.input = word.clone();
+            app.query_db(word);
+        }
     }
-    app.create(dicpath.join([app.databases.first().unwrap(), DICEXTENSION].concat()));
     #[cfg(feature = "clipboard")]
     if let Ok(cb) = <ClipboardContext as ClipboardProvider>::new() {
         app.clipboard = Some(cb);
     }
+    let mut signals = signal_hook::consts::TERM_SIGNALS.to_vec();
+    #[cfg(unix)]
+    signals.push(signal_hook::consts::SIGHUP);
+    for signal in signals {
+        signal_hook::flag::register(signal, Arc::clone(&app.terminate))?;
+    }
     app.run(terminal)?;
+    app.save_state();
 
     restore_terminal()?;
 
+    if app.databases.is_empty() {
+        return Err(format!(
+            "No dictionaries found in '{}'. Copy a dictionary there and start dicrs again.",
+            app.dicpath.display()
+        )
+        .into());
+    }
     Ok(())
 }
 
@@ -116,408 +482,2813 @@ fn init_terminal() -> color_eyre::Result<Terminal<impl Backend>> {
 
 fn restore_terminal() -> color_eyre::Result<()> {
     disable_raw_mode()?;
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     Ok(())
 }
 
 impl App {
-    fn default(dicpath: PathBuf, mode: Mode) -> Self {
+    /// A new app over the dictionaries in `dicpath`. Fails when the Leitner
+    /// deck or the favorites in `datapath` cannot be opened.
+    fn default(
+        config: Config,
+        datapath: PathBuf,
+        dicpath: PathBuf,
+        mode: Mode,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (databases, database_paths) = dictionary::scan(&dicpath, &config);
+        let open_error =
+            |path: &Path, e: rusqlite::Error| format!("Could not open '{}': {}", path.display(), e);
         #[cfg(feature = "leitner")]
-        let home_dir = std::env::var("HOME").expect("HOME environment variable not set");
-        let mut databases: Vec<String> = Vec::new();
-        for entry in fs::read_dir(&dicpath).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            let filename = path.file_name().ok_or("No filename").unwrap().to_str();
-            let filename = filename.unwrap().to_string().replace(DICEXTENSION, "");
-            databases.push(filename);
-        }
-        Self {
+        let leitner = {
+            let path = datapath.join("leitner.sqlite");
+            Leitner::new(path.clone(), &config.leitner).map_err(|e| open_error(&path, e))?
+        };
+        let favorites = {
+            let path = datapath.join("favorites.sqlite");
+            Favorites::new(&path).map_err(|e| open_error(&path, e))?
+        };
+        Ok(Self {
             input: String::new(),
             definition: String::new(),
             selected_index: usize::default(),
             dictionary_index: usize::default(),
+            previous_dictionary_index: usize::default(),
+            recent_dictionaries: VecDeque::new(),
+            history: Vec::new(),
+            history_cursor: 0,
             dicpath,
-            database_path: PathBuf::new(),
-            conn: Connection::open_in_memory().unwrap(),
-            word_index: Vec::new(),
-            databases,
             #[cfg(feature = "leitner")]
-            leitner: Leitner::new(
-                path::Path::new(&home_dir).join(".local/share/dicrs/leitner.sqlite"),
+            leitner,
+            favorites,
+            datapath,
+            database_path: PathBuf::new(),
+            dictionary: Dictionary::from_connection(
+                Connection::open_in_memory().unwrap(),
+                &DictionaryConfig::default(),
             )
             .unwrap(),
+            word_index: Vec::new(),
+            word_index_folded: Vec::new(),
+            anagram_keys: Vec::new(),
+            rowids: Vec::new(),
+            index_total: 0,
+            duplicates: HashMap::new(),
+            filter: None,
+            filter_cursor: 0,
+            filter_kind: FilterKind::Input,
+            expanded: false,
+            see_also: Vec::new(),
+            link: None,
+            suggestions: None,
+            suggestion: 0,
+            live_search: config.ui.live_search,
+            live_query: String::new(),
+            input_edited: None,
+            definition_pattern: None,
+            full_text_mode: false,
+            full_text: None,
+            search_all_mode: false,
+            is_favorite: false,
+            stemmed_from: None,
+            databases,
+            database_paths,
+            standalone: false,
             mode,
+            overlay: None,
+            recording: None,
+            macro_keys: Vec::new(),
+            keymap: Keymap::from_config(&config),
+            normal_mode: false,
+            pending_keys: Vec::new(),
+            pending_since: Instant::now(),
+            status: None,
+            count: None,
+            search_mode: SearchMode::default(),
+            whole_word: false,
+            show_raw: false,
+            speech: None,
+            edit_requested: false,
             scroll: 0,
+            config,
             #[cfg(feature = "clipboard")]
             clipboard: None,
-        }
+            definition_area: Rect::default(),
+            screen: Buffer::empty(Rect::default()),
+            terminate: Arc::new(AtomicBool::new(false)),
+        })
     }
 
+    /// Opens the dictionary at `db_path`. One that is missing or cannot be
+    /// read leaves an empty index and says why in the definition pane and
+    /// the status line.
     fn create(&mut self, db_path: PathBuf) {
         self.selected_index = 0;
         self.database_path.clone_from(&db_path);
-        self.conn = Connection::open(&db_path).unwrap();
-        self.word_index = self.retrieve_db_index();
-        self.update_by_index(0);
+        let opened = match db_path.is_file() {
+            true => Dictionary::open(&db_path, self.current_dictionary())
+                .and_then(|dictionary| Ok((dictionary.count()?, dictionary))),
+            false => Err(rusqlite::Error::InvalidPath(db_path.clone())),
+        };
+        let error = match opened {
+            Ok((count, dictionary)) => {
+                self.dictionary = dictionary;
+                self.index_total = count;
+                None
+            }
+            Err(e) => {
+                self.dictionary = Dictionary::from_connection(
+                    Connection::open_in_memory().unwrap(),
+                    &DictionaryConfig::default(),
+                )
+                .unwrap();
+                self.index_total = 0;
+                Some(match e {
+                    rusqlite::Error::InvalidPath(_) => {
+                        format!("'{}' does not exist", db_path.display())
+                    }
+                    e => format!("Could not open '{}': {}", db_path.display(), e),
+                })
+            }
+        };
+        self.full_text = None;
+        self.stemmed_from = None;
+        self.rowids.clear();
+        self.word_index.clear();
+        self.word_index_folded.clear();
+        self.anagram_keys.clear();
+        self.duplicates.clear();
+        self.load_index_chunk();
+        if self.filter.is_some() {
+            self.finish_loading();
+            self.apply_filter();
+        } else {
+            self.update_by_index(0);
+        }
+        if let Some(error) = error {
+            self.definition.clone_from(&error);
+            self.scroll = 0;
+            self.status = Some((error, Instant::now()));
+        }
     }
 
-    fn retrieve_db_index(&self) -> Vec<String> {
-        let mut stmt = self.conn.prepare("SELECT word FROM dictionary").unwrap();
-        let mut rows = stmt.query([]).unwrap();
-        let mut index = Vec::new();
-        while let Ok(Some(row)) = rows.next() {
-            index.push(row.get(0).unwrap());
-        }
-        index
+    fn index_loaded(&self) -> bool {
+        self.word_index.len() >= self.index_total
     }
 
-    fn update_by_index(&mut self, i: isize) {
-        self.selected_index = (self.selected_index as isize + i)
-            .clamp(0, self.word_index.len() as isize - 1) as usize;
-        self.definition = self.query_db_by_index(self.selected_index + 1).definition;
+    /// Reads the next chunk of the index. Once it is complete, finds the
+    /// headwords with several entries. Returns whether anything was left to
+    /// read.
+    fn load_index_chunk(&mut self) -> bool {
+        if self.index_loaded() {
+            return false;
+        }
+        let after = self.rowids.last().copied().unwrap_or(i64::MIN);
+        match self.dictionary.index_after(after, INDEX_CHUNK) {
+            Ok((rowids, words)) if !rowids.is_empty() => {
+                self.word_index_folded
+                    .extend(words.iter().map(|w| search::fold(w)));
+                self.rowids.extend(rowids);
+                self.word_index.extend(words);
+            }
+            _ => self.index_total = self.word_index.len(),
+        }
+        if self.index_loaded() {
+            let mut rowids_by_word: HashMap<String, Vec<i64>> = HashMap::new();
+            for (word, &rowid) in self.word_index.iter().zip(&self.rowids) {
+                rowids_by_word.entry(word.clone()).or_default().push(rowid);
+            }
+            rowids_by_word.retain(|_, rowids| rowids.len() > 1);
+            self.duplicates = rowids_by_word;
+            let selected = self.word_index.get(self.selected_index);
+            if selected.is_some_and(|word| self.duplicates.contains_key(word)) {
+                self.update_by_index(0);
+            }
+        }
+        true
     }
 
-    fn change_database(&mut self, i: isize) {
-        let x = self.dictionary_index as isize + i;
-        self.dictionary_index = if x == -1 {
-            self.databases.len() - 1
-        } else if x > self.databases.len() as isize - 1 {
-            0
-        } else {
-            (x % self.databases.len() as isize) as usize
-        };
-        self.create(
-            self.dicpath.join(
-                [
-                    self.databases.get(self.dictionary_index).unwrap(),
-                    DICEXTENSION,
-                ]
-                .concat(),
-            ),
-        );
+    /// Reads the rest of the index, for the actions that need all of it.
+    fn finish_loading(&mut self) {
+        while self.load_index_chunk() {}
     }
 
-    fn query_db(&mut self, word: String) {
-        let sql = "SELECT ROWID, definition FROM dictionary WHERE word LIKE :query";
-        let wild_card_query = format!("{}%", word);
-        let mut stmt = self.conn.prepare(sql).unwrap();
-        let mut rows = stmt
-            .query_map([(wild_card_query)], |row| {
-                let rowid: u32 = row.get(0)?;
-                let def: String = row.get(1)?;
-                Ok((rowid, def))
-            })
-            .unwrap();
+    fn current_dictionary(&self) -> &DictionaryConfig {
+        match self.databases.get(self.dictionary_index) {
+            Some(name) => self.config.dictionary(name),
+            None => self.config.dictionary(""),
+        }
+    }
 
-        if let Some(row) = rows.next() {
-            let (rowid, def) = row.unwrap();
-            self.selected_index = (rowid - 1) as usize;
-            self.definition = def.replace('\r', "\n");
-        } else {
-            self.definition = "Not found!".to_string();
+    fn refresh(&mut self) {
+        let current_word = self.word_index.get(self.selected_index).cloned();
+        self.create(self.database_path.clone());
+        self.finish_loading();
+        let position =
+            current_word.and_then(|word| self.word_index.iter().position(|w| *w == word));
+        match position {
+            Some(i) => self.select_index(i),
+            None if !self.input.is_empty() => self.query_db(self.input.to_string()),
+            None => {}
         }
-        self.scroll = 0;
     }
-    fn query_db_by_index(&mut self, word_index: usize) -> DicEntry {
-        let sql = "SELECT ROWID, word, definition FROM dictionary WHERE ROWID = :query";
-        let wild_card_query = word_index.to_string();
-        let mut stmt = self.conn.prepare(sql).unwrap();
-        let mut res = DicEntry::default();
-        let mut rows = stmt
-            .query_map([(wild_card_query)], |row| {
-                let rowid: u32 = row.get(0)?;
-                let word: String = row.get(1)?;
-                let def: String = row.get(2)?;
-                Ok((rowid, word, def))
-            })
-            .unwrap();
 
-        if let Some(row) = rows.next() {
-            let (rowid, word, def) = row.unwrap();
-            res.index = (rowid - 1) as usize;
-            res.word = word;
-            res.definition = def.replace('\r', "\n");
-        } else {
-            res.definition = "Not found!".to_string();
+    fn rescan(&mut self) {
+        (self.databases, self.database_paths) = dictionary::scan(&self.dicpath, &self.config);
+        self.dictionary_index = 0;
+        self.previous_dictionary_index = 0;
+        self.recent_dictionaries.clear();
+        self.remember_dictionary();
+        self.history.clear();
+        self.history_cursor = 0;
+        if !self.databases.is_empty() {
+            self.create(self.database_file(0));
         }
-        self.scroll = 0;
-        res
     }
 
-    fn run(&mut self, mut terminal: Terminal<impl Backend>) -> io::Result<()> {
-        loop {
-            self.draw(&mut terminal)?;
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    use KeyCode::*;
-                    #[cfg(feature = "leitner")]
-                    if self.mode == Mode::Leitner {
-                        match (key.code, key.modifiers) {
-                            (Char('c'), KeyModifiers::CONTROL) => return Ok(()),
-                            (Char('y'), KeyModifiers::NONE) => {
-                                let result = self.leitner.review(true);
-                                if result.is_ok() {
-                                    self.leitner.next()
-                                }
-                            }
-                            (Char('n'), KeyModifiers::NONE) => {
-                                let result = self.leitner.review(false);
-                                if result.is_ok() {
-                                    self.leitner.next()
-                                }
-                            }
-                            (Char('l'), KeyModifiers::ALT) => {
-                                self.mode = Mode::Default;
-                                self.update_by_index(0);
-                            }
-                            (Char('m'), KeyModifiers::ALT) => {
-                                self.mode = Mode::Minimal;
-                                self.update_by_index(0);
-                            }
-                            (Up, KeyModifiers::NONE) => self.leitner.update_index_by(-1),
-                            (Down, KeyModifiers::NONE) => self.leitner.update_index_by(1),
-                            (PageDown, KeyModifiers::NONE) => {
-                                self.scroll += 1;
-                            }
-                            (PageUp, KeyModifiers::NONE) => {
-                                self.scroll = self.scroll.saturating_sub(1)
-                            }
-                            (Enter, KeyModifiers::NONE) | (Char(' '), KeyModifiers::NONE) => {
-                                self.definition =
-                                    self.leitner.get_definition(self.leitner.selected_index);
-                            }
-                            _ => {}
-                        }
-                        continue;
-                    }
-                    match (key.code, key.modifiers) {
-                        (Char('c'), KeyModifiers::CONTROL) => return Ok(()),
-                        #[cfg(feature = "clipboard")]
-                        (Char('y'), KeyModifiers::CONTROL) => {
-                            if let Some(ref mut cb) = self.clipboard {
-                                cb.set_contents(self.definition.to_owned()).unwrap()
-                            }
-                        }
-                        (Char('m'), KeyModifiers::ALT) => {
-                            self.mode = if self.mode != Mode::Minimal {
-                                Mode::Minimal
-                            } else {
-                                Mode::Default
-                            };
-                        }
-                        #[cfg(feature = "leitner")]
-                        (Char('l'), KeyModifiers::ALT) => {
-                            self.mode = Mode::Leitner;
-                            self.leitner.next();
-                            self.definition =
-                                "Enter or Space: Show the definition of the selected word.\n\
-Y: Mark the current word as \"correct\" and review it again later.\n\
-N: Mark the current word as \"incorrect\" and review it sooner.\n\
-Alt + L / Alt + M: Switch to the Default / Minimal Mode.\n\
-↑: Move the selection up in the word index.\n\
-↓: Move the selection down in the word index.\n"
-                                    .to_string();
-                        }
-                        #[cfg(feature = "leitner")]
-                        (Char('`'), KeyModifiers::NONE) => {
-                            let entry = self.query_db_by_index(self.selected_index + 1);
-                            let _ = self.leitner.add(&entry.word, &entry.definition);
-                        }
-                        (Up, KeyModifiers::NONE) => self.update_by_index(-1),
-                        (Down, KeyModifiers::NONE) => self.update_by_index(1),
-                        (Up, KeyModifiers::SHIFT) => self.update_by_index(-10),
-                        (Down, KeyModifiers::SHIFT) => self.update_by_index(10),
-                        (Left, KeyModifiers::NONE) => {
-                            self.change_database(-1);
-                            self.query_db(self.input.to_string());
-                        }
-                        (Right, KeyModifiers::NONE) => {
-                            self.change_database(1);
-                            self.query_db(self.input.to_string());
-                        }
-                        (PageDown, KeyModifiers::NONE) => {
-                            self.scroll += 1;
-                        }
-                        (PageUp, KeyModifiers::NONE) => self.scroll = self.scroll.saturating_sub(1),
-                        (Enter, KeyModifiers::NONE) => {
-                            self.query_db(self.input.to_string());
-                        }
-                        (Backspace, KeyModifiers::NONE) => {
-                            self.input.pop();
-                        }
-                        (Backspace, KeyModifiers::ALT) => delete_last_word(&mut self.input),
-                        (Char(c), _) => self.input.push(c),
-                        _ => {}
-                    }
-                }
+    fn update_by_index(&mut self, i: isize) {
+        if let Some(filter) = &self.filter {
+            if filter.is_empty() {
+                self.definition = "Not found!".to_string();
+                self.see_also.clear();
+                self.scroll = 0;
+                return;
+            }
+            self.filter_cursor =
+                (self.filter_cursor as isize + i).clamp(0, filter.len() as isize - 1) as usize;
+            self.selected_index = filter[self.filter_cursor];
+        } else if self.word_index.is_empty() {
+            self.definition = "Not found!".to_string();
+            self.see_also.clear();
+            self.scroll = 0;
+            return;
+        } else {
+            if self.selected_index as isize + i >= self.word_index.len() as isize {
+                // Moving past the part read so far, e.g. to the bottom.
+                self.finish_loading();
             }
+            self.selected_index = (self.selected_index as isize + i)
+                .clamp(0, self.word_index.len() as isize - 1)
+                as usize;
         }
+        let entry = self.query_db_by_index(self.selected_index);
+        self.show_entry(entry);
+        self.is_favorite = self
+            .favorites
+            .contains(
+                &self.databases[self.dictionary_index],
+                &self.word_index[self.selected_index],
+            )
+            .unwrap_or(false);
     }
 
-    fn draw(&mut self, terminal: &mut Terminal<impl Backend>) -> io::Result<()> {
-        terminal.draw(|f| ui(f, self))?;
-        Ok(())
+    /// Shows the definition and related headwords of `entry`.
+    fn show_entry(&mut self, entry: Entry) {
+        self.suggestions = None;
+        self.stemmed_from = None;
+        self.definition = entry.definition;
+        self.see_also = entry.see_also;
+        self.link = None;
     }
-}
 
-fn ui(f: &mut Frame, app: &mut App) {
-    match app.mode {
-        Mode::Default => render_default_mode(f, app),
-        Mode::Minimal => render_minimal_mode(f, app),
-        #[cfg(feature = "leitner")]
-        Mode::Leitner => render_leitner_mode(f, app),
+    /// Selects the next suggestion, or else the next related headword,
+    /// wrapping around to the first.
+    fn next_link(&mut self) {
+        if let Some((_, words)) = &self.suggestions {
+            self.suggestion = (self.suggestion + 1) % words.len();
+        } else if !self.see_also.is_empty() {
+            self.link = Some(self.link.map_or(0, |link| (link + 1) % self.see_also.len()));
+        }
     }
-}
 
-fn render_default_mode(f: &mut Frame, app: &mut App) {
-    let vertical = Layout::vertical([
-        Constraint::Length(3),
-        Constraint::Length((min(4, app.databases.len()) + 2) as u16),
-        Constraint::Min(5),
-    ]);
-    let [input_area, databases_area, rest_area] = vertical.areas(f.area());
+    /// Looks up the selected related headword.
+    fn follow_link(&mut self) {
+        let Some(word) = self.link.and_then(|link| self.see_also.get(link)).cloned() else {
+            return;
+        };
+        self.input = word;
+        self.on_input_changed();
+        self.query_db(self.input.clone());
+    }
 
-    let vertical = Layout::horizontal([Constraint::Length(18), Constraint::Min(0)]);
-    let [words_area, definition_area] = vertical.areas(rest_area);
+    fn select_index(&mut self, index: usize) {
+        self.selected_index = index;
+        self.sync_filter_cursor();
+        self.update_by_index(0);
+    }
 
-    let input = Paragraph::new(app.input.as_str())
-        .style(Style::default().fg(Color::LightCyan))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(Color::Reset))
-                .title("Input"),
-        );
-    f.render_widget(input, input_area);
+    fn sync_filter_cursor(&mut self) {
+        if let Some(filter) = &self.filter {
+            if let Some(cursor) = filter.iter().position(|&i| i == self.selected_index) {
+                self.filter_cursor = cursor;
+            }
+        }
+    }
 
-    let databases = List::new(app.databases.clone())
-        .block(Block::default().borders(Borders::ALL).title("Dictionaries"))
-        .highlight_style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        );
-    let mut state = ListState::default().with_selected(Some(app.dictionary_index));
-    f.render_stateful_widget(databases, databases_area, &mut state);
+    /// Turns filtering by `kind` on, or off when it is already on.
+    fn toggle_filter(&mut self, kind: FilterKind) {
+        if self.filter.is_some() && self.filter_kind == kind {
+            self.clear_filter();
+        } else {
+            self.filter_kind = kind;
+            self.apply_filter();
+        }
+    }
 
-    let height = words_area.as_size().height as usize - 2;
-    let before = max(app.selected_index as isize - height as isize / 2, 0) as usize;
-    let after = min(app.selected_index + height, app.word_index.len());
-    let word_index: Vec<String> = (app.word_index[before..after]).to_vec();
-    let word_index = List::new(word_index)
-        .block(Block::default().borders(Borders::ALL).title("Index"))
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::White));
-    let mut state = ListState::default().with_selected(Some(min(app.selected_index, height / 2)));
-    f.render_stateful_widget(word_index, words_area, &mut state);
-    let max_scroll = calculate_max_scroll(
-        app.definition.as_str(),
-        definition_area.width,
-        definition_area.height,
-    );
-    app.scroll = app.scroll.min(max_scroll);
-    let definition = Paragraph::new(app.definition.as_str())
-        .block(Block::default().borders(Borders::ALL).title("Definition"))
-        .scroll((app.scroll, 0))
-        .wrap(Wrap { trim: true });
-    f.render_widget(definition, definition_area);
-}
+    fn clear_filter(&mut self) {
+        self.filter = None;
+        self.definition_pattern = None;
+        self.update_by_index(0);
+    }
 
-fn render_minimal_mode(f: &mut Frame, app: &mut App) {
-    let vertical = Layout::vertical([
-        Constraint::Length(3),
-        Constraint::Min(5),
-        Constraint::Length(1),
-    ]);
-    let [input_area, definition_area, status_area] = vertical.areas(f.area());
+    /// Filters the index by the definitions matching the input as a regular
+    /// expression, or turns that filter off.
+    fn search_definitions(&mut self) {
+        if self.filter.is_some() && self.filter_kind == FilterKind::Definitions {
+            self.toggle_filter(FilterKind::Definitions);
+            return;
+        }
+        match Regex::new(&self.input) {
+            Ok(pattern) => {
+                self.definition_pattern = Some(pattern);
+                self.toggle_filter(FilterKind::Definitions);
+            }
+            Err(e) => {
+                self.definition = format!("Invalid pattern: {}", e);
+                self.scroll = 0;
+            }
+        }
+    }
 
-    let input = Paragraph::new(app.input.as_str())
-        .style(Style::default().fg(Color::LightCyan))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(Color::Reset))
-                .title("Input"),
+    /// Filters the index by the definitions containing the input, ignoring
+    /// case, and highlights the input in them.
+    fn reverse_search(&mut self) {
+        if self.filter.is_some() && self.filter_kind == FilterKind::Reverse {
+            self.toggle_filter(FilterKind::Reverse);
+            return;
+        }
+        if self.input.is_empty() {
+            return;
+        }
+        self.definition_pattern = self.reverse_pattern();
+        self.toggle_filter(FilterKind::Reverse);
+    }
+
+    /// The input as a case-insensitive pattern for reverse searches, which
+    /// only matches it as a whole word when `whole_word` is set.
+    fn reverse_pattern(&self) -> Option<Regex> {
+        let boundary = |c: Option<char>| match c {
+            Some(c) if self.whole_word && (c.is_alphanumeric() || c == '_') => r"\b",
+            _ => "",
+        };
+        let pattern = format!(
+            "(?i){}{}{}",
+            boundary(self.input.chars().next()),
+            regex::escape(&self.input),
+            boundary(self.input.chars().next_back())
         );
-    f.render_widget(input, input_area);
-    let max_scroll = calculate_max_scroll(
-        app.definition.as_str(),
-        definition_area.width,
-        definition_area.height,
-    );
-    app.scroll = app.scroll.min(max_scroll);
+        Regex::new(&pattern).ok()
+    }
 
-    let definition = Paragraph::new(app.definition.as_str())
-        .block(Block::default().borders(Borders::ALL).title("Definition"))
-        .scroll((app.scroll, 0))
-        .wrap(Wrap { trim: true });
-    f.render_widget(definition, definition_area);
+    /// Switches the input between searching headwords and searching the
+    /// definitions through the full-text index.
+    fn toggle_full_text(&mut self) {
+        self.full_text_mode = !self.full_text_mode;
+        if self.full_text_mode {
+            self.build_full_text();
+            self.full_text_search();
+        } else if self.filter.is_some() && self.filter_kind == FilterKind::FullText {
+            self.clear_filter();
+        }
+    }
 
-    let status = Paragraph::new(format!(
-        "db: {}",
-        app.databases.get(app.dictionary_index).unwrap()
-    ))
-    .block(Block::default());
-    f.render_widget(status, status_area);
-}
+    /// Lists the entries whose definition has the words of the input.
+    fn full_text_search(&mut self) {
+        if self.input.trim().is_empty() {
+            return;
+        }
+        self.finish_loading();
+        self.filter_kind = FilterKind::FullText;
+        self.apply_filter();
+    }
 
-#[cfg(feature = "leitner")]
-fn render_leitner_mode(f: &mut Frame, app: &mut App) {
-    let vertical = Layout::horizontal([Constraint::Length(18), Constraint::Min(24)]);
-    let [words_area, definition_area] = vertical.areas(f.area());
-    if app.leitner.word_index.is_empty() {
-        let empty_list = List::new(vec![Span::from("Empty")])
-            .block(Block::default().borders(Borders::ALL).title("Index"));
-        f.render_widget(empty_list, words_area);
+    /// Opens the full-text index, building it first when it is missing or
+    /// older than the dictionary. Only explicit searches build it, as that
+    /// takes a while for large dictionaries.
+    fn build_full_text(&mut self) {
+        if self.full_text.is_some() {
+            return;
+        }
+        match self.open_full_text(true) {
+            Ok(full_text) => self.full_text = full_text,
+            Err(e) => {
+                let message = format!("Could not build the full-text index: {}", e);
+                self.status = Some((message, Instant::now()));
+            }
+        }
+    }
 
-        let empty_definition = Paragraph::new("Use ~ (`) key to add a word to Leitner.")
-            .block(Block::default().borders(Borders::ALL).title("Definition"))
-            .wrap(Wrap { trim: true });
-        f.render_widget(empty_definition, definition_area);
+    /// Row ids of the entries whose definition has the words of the input,
+    /// once the full-text index is up to date.
+    fn full_text_matches(&mut self) -> Vec<i64> {
+        if self.full_text.is_none() {
+            match self.open_full_text(false) {
+                Ok(Some(full_text)) => self.full_text = Some(full_text),
+                Ok(None) => {
+                    let message = "Press Enter to index the definitions".to_string();
+                    self.status = Some((message, Instant::now()));
+                    return Vec::new();
+                }
+                Err(e) => {
+                    let message = format!("Could not open the full-text index: {}", e);
+                    self.status = Some((message, Instant::now()));
+                    return Vec::new();
+                }
+            }
+        }
+        let Some(full_text) = &self.full_text else {
+            return Vec::new();
+        };
+        match full_text.search(&self.input, FULL_TEXT_LIMIT) {
+            Ok(rowids) => {
+                if rowids.len() == FULL_TEXT_LIMIT {
+                    let message = format!("Showing the first {} entries", FULL_TEXT_LIMIT);
+                    self.status = Some((message, Instant::now()));
+                }
+                rowids
+            }
+            Err(e) => {
+                self.status = Some((format!("Full-text search failed: {}", e), Instant::now()));
+                Vec::new()
+            }
+        }
+    }
 
-        return;
+    /// The full-text index of the dictionary, or `None` when it is missing or
+    /// out of date and not to be built.
+    fn open_full_text(&mut self, build: bool) -> Result<Option<FullText>, Box<dyn Error>> {
+        let mut full_text = FullText::open(&full_text_path(&self.datapath, &self.database_path)?)?;
+        let stamp = fulltext::stamp(&self.database_path);
+        if !full_text.is_current(&stamp)? {
+            if !build {
+                return Ok(None);
+            }
+            let count = full_text.build(&self.dictionary, &stamp)?;
+            let message = format!("Indexed {} definitions", count);
+            self.status = Some((message, Instant::now()));
+        }
+        Ok(Some(full_text))
     }
-    let height = words_area.as_size().height as usize - 2;
-    let before = max(app.leitner.selected_index as isize - height as isize / 2, 0) as usize;
-    let after = min(
-        app.leitner.selected_index + height,
-        app.leitner.word_index.len(),
-    );
-    let word_index: Vec<String> = app.leitner.word_index[before..after].to_vec();
+
+    fn toggle_favorite(&mut self) {
+        let Some(word) = self.word_index.get(self.selected_index).cloned() else {
+            return;
+        };
+        let dictionary = self.databases[self.dictionary_index].clone();
+        match self.favorites.toggle(&dictionary, &word) {
+            Ok(is_favorite) => self.is_favorite = is_favorite,
+            Err(e) => {
+                self.definition = format!("Could not update the favorites: {}", e);
+                self.scroll = 0;
+                return;
+            }
+        }
+        if self.filter.is_some() && self.filter_kind == FilterKind::Favorites {
+            let index = self.selected_index;
+            self.apply_filter();
+            self.select_index(index);
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        self.finish_loading();
+        // Only the definition filters highlight their pattern.
+        if !matches!(
+            self.filter_kind,
+            FilterKind::Definitions | FilterKind::Reverse
+        ) {
+            self.definition_pattern = None;
+        }
+        let filter = match self.filter_kind {
+            FilterKind::Input => {
+                let (input, whole_word) = (self.input.clone(), self.whole_word);
+                let (words, query, mode) = self.search_words(&input);
+                search::all_matches(words, &query, mode, whole_word)
+            }
+            FilterKind::Favorites => {
+                let favorites = self
+                    .favorites
+                    .words(&self.databases[self.dictionary_index])
+                    .unwrap_or_default();
+                self.word_index
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, word)| favorites.contains(*word))
+                    .map(|(i, _)| i)
+                    .collect()
+            }
+            FilterKind::Definitions | FilterKind::Reverse => {
+                let limit = match self.filter_kind {
+                    FilterKind::Reverse => REVERSE_SEARCH_LIMIT,
+                    _ => usize::MAX,
+                };
+                let rowids = match &self.definition_pattern {
+                    Some(pattern) => self.dictionary.matching_definitions(pattern, limit),
+                    None => Ok(Vec::new()),
+                };
+                let rowids = rowids.unwrap_or_default();
+                if rowids.len() == limit {
+                    let message = format!("Showing the first {} entries", limit);
+                    self.status = Some((message, Instant::now()));
+                }
+                rowids
+                    .iter()
+                    .filter_map(|rowid| self.rowids.binary_search(rowid).ok())
+                    .collect()
+            }
+            FilterKind::FullText => self
+                .full_text_matches()
+                .iter()
+                .filter_map(|rowid| self.rowids.binary_search(rowid).ok())
+                .collect(),
+        };
+        self.filter = Some(filter);
+        self.filter_cursor = 0;
+        self.update_by_index(0);
+    }
+
+    fn on_input_changed(&mut self) {
+        if self.filter.is_some() && self.filter_kind == FilterKind::Input {
+            self.apply_filter();
+        }
+    }
+
+    fn change_database(&mut self, i: isize) {
+        let previous = self.dictionary_index;
+        let x = self.dictionary_index as isize + i;
+        self.dictionary_index = if x == -1 {
+            self.databases.len() - 1
+        } else if x > self.databases.len() as isize - 1 {
+            0
+        } else {
+            (x % self.databases.len() as isize) as usize
+        };
+        if self.dictionary_index != previous {
+            self.previous_dictionary_index = previous;
+        }
+        self.remember_dictionary();
+        self.create(self.database_file(self.dictionary_index));
+        self.save_state();
+    }
+
+    /// Moves the current dictionary to the front of the recently used ones.
+    fn remember_dictionary(&mut self) {
+        self.recent_dictionaries
+            .retain(|&index| index != self.dictionary_index);
+        self.recent_dictionaries.push_front(self.dictionary_index);
+        self.recent_dictionaries.truncate(RECENT_DICTIONARIES);
+    }
+
+    /// Switches to the next recently used dictionary. The current one goes
+    /// to the back, so that repeating this visits each of them in turn.
+    fn cycle_recent_dictionary(&mut self) {
+        if self.recent_dictionaries.len() < 2 {
+            return;
+        }
+        self.recent_dictionaries.rotate_left(1);
+        let index = self.recent_dictionaries[0];
+        self.change_database(index as isize - self.dictionary_index as isize);
+        self.query_db(self.input.to_string());
+    }
+
+    fn save_state(&self) {
+        let path = self.datapath.join(STATE_FILE);
+        let dictionary = match self.standalone {
+            true => State::load(&path).dictionary,
+            false => self.databases.get(self.dictionary_index).cloned(),
+        };
+        let state = State {
+            dictionary,
+            search_mode: Some(self.search_mode),
+        };
+        let _ = state.save(&path);
+    }
+
+    fn database_file(&self, dictionary_index: usize) -> PathBuf {
+        self.database_paths[dictionary_index].clone()
+    }
+
+    fn search_all(&mut self) {
+        if self.input.is_empty() {
+            return;
+        }
+        let query = self.search_mode.fold(&self.input);
+        let mut hits = Vec::new();
+        for dictionary_index in 0..self.databases.len() {
+            let config = self.config.dictionary(&self.databases[dictionary_index]);
+            let Ok(dictionary) = Dictionary::open(&self.database_file(dictionary_index), config)
+            else {
+                continue;
+            };
+            let Ok(rows) = dictionary.search(&self.input, self.search_mode, 50) else {
+                continue;
+            };
+            hits.extend(
+                rows.into_iter()
+                    .filter(|(_, word)| {
+                        self.search_mode
+                            .matches(&search::fold(word), &query, self.whole_word)
+                    })
+                    .map(|(rowid, word)| SearchHit {
+                        dictionary_index,
+                        rowid,
+                        word,
+                    }),
+            );
+        }
+        // Dictionaries in their listed order, then entries in storage order,
+        // so the overlay can group hits by dictionary.
+        hits.sort_by_key(|hit| (hit.dictionary_index, hit.rowid));
+        self.overlay = Some(Overlay::Results { hits, cursor: 0 });
+    }
+
+    fn open_hit(&mut self, hit: &SearchHit) {
+        self.remember_visit();
+        if hit.dictionary_index != self.dictionary_index {
+            self.change_database(hit.dictionary_index as isize - self.dictionary_index as isize);
+        }
+        self.finish_loading();
+        if let Ok(index) = self.rowids.binary_search(&hit.rowid) {
+            self.select_index(index);
+        }
+        self.remember_visit();
+    }
+
+    /// Adds the selected entry to the history after the current one, unless
+    /// it is the current one. Entries after the current one are dropped.
+    fn remember_visit(&mut self) {
+        if self.word_index.is_empty() {
+            return;
+        }
+        let visit = (self.dictionary_index, self.selected_index);
+        if self.history.get(self.history_cursor) == Some(&visit) {
+            return;
+        }
+        self.history.truncate(self.history_cursor + 1);
+        self.history.push(visit);
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        self.history_cursor = self.history.len() - 1;
+    }
+
+    /// Goes `step` entries back or forward in the history, switching the
+    /// dictionary when needed.
+    fn step_history(&mut self, step: isize) {
+        self.remember_visit();
+        let Some(cursor) = self
+            .history_cursor
+            .checked_add_signed(step)
+            .filter(|&cursor| cursor < self.history.len())
+        else {
+            return;
+        };
+        let (dictionary_index, index) = self.history[cursor];
+        if dictionary_index >= self.databases.len() {
+            return;
+        }
+        self.history_cursor = cursor;
+        if dictionary_index != self.dictionary_index {
+            self.change_database(dictionary_index as isize - self.dictionary_index as isize);
+        }
+        self.finish_loading();
+        if index < self.word_index.len() {
+            self.select_index(index);
+        }
+    }
+
+    /// Switches to the closest dictionary in the `step` direction with an
+    /// entry for the selected headword, and selects that entry.
+    fn dictionary_with_word(&mut self, step: isize) {
+        let Some(word) = self.word_index.get(self.selected_index).cloned() else {
+            return;
+        };
+        if word.is_empty() {
+            return;
+        }
+        let len = self.databases.len() as isize;
+        for offset in 1..len {
+            let dictionary_index =
+                (self.dictionary_index as isize + step * offset).rem_euclid(len) as usize;
+            let config = self.config.dictionary(&self.databases[dictionary_index]);
+            let Ok(dictionary) = Dictionary::open(&self.database_file(dictionary_index), config)
+            else {
+                continue;
+            };
+            let rowid = dictionary
+                .search(&word, SearchMode::Exact, 1)
+                .ok()
+                .and_then(|rows| rows.first().map(|&(rowid, _)| rowid));
+            if let Some(rowid) = rowid {
+                self.open_hit(&SearchHit {
+                    dictionary_index,
+                    rowid,
+                    word,
+                });
+                return;
+            }
+        }
+        self.status = Some((
+            format!("No other dictionary has '{}'", word),
+            Instant::now(),
+        ));
+    }
+
+    /// Handles a key while an overlay is open. Returns whether to quit.
+    fn handle_overlay_key(&mut self, key: KeyEvent) -> bool {
+        use KeyCode::*;
+        let palette = match &self.overlay {
+            Some(Overlay::Palette { input, .. }) => self.palette_entries(input),
+            _ => Vec::new(),
+        };
+        let Some(overlay) = self.overlay.as_mut() else {
+            return false;
+        };
+        if key.code == Backspace && self.config.ui.backspace_escapes {
+            let empty = match overlay {
+                Overlay::JumpPercent { input } | Overlay::Palette { input, .. } => input.is_empty(),
+                Overlay::NewEntry {
+                    step: EntryStep::Word,
+                    word,
+                    ..
+                } => word.is_empty(),
+                Overlay::NewEntry {
+                    step: EntryStep::Definition,
+                    definition,
+                    ..
+                } => definition.is_empty(),
+                _ => true,
+            };
+            if empty {
+                self.overlay = None;
+                return false;
+            }
+        }
+        match overlay {
+            Overlay::Results { hits, cursor } => match key.code {
+                Up => *cursor = cursor.saturating_sub(1),
+                Down => *cursor = min(*cursor + 1, hits.len().saturating_sub(1)),
+                Enter => {
+                    let hits = std::mem::take(hits);
+                    let cursor = *cursor;
+                    self.overlay = None;
+                    if let Some(hit) = hits.get(cursor) {
+                        self.open_hit(hit);
+                    }
+                }
+                Esc => self.overlay = None,
+                _ => {}
+            },
+            Overlay::Help { .. } => self.overlay = None,
+            Overlay::Palette { input, cursor } => match key.code {
+                Up => *cursor = cursor.saturating_sub(1),
+                Down => *cursor = min(*cursor + 1, palette.len().saturating_sub(1)),
+                Char(c) => {
+                    input.push(c);
+                    *cursor = 0;
+                }
+                Backspace => {
+                    input.pop();
+                    *cursor = 0;
+                }
+                Enter => {
+                    let action = palette.get(*cursor).map(|(action, _)| *action);
+                    self.overlay = None;
+                    if let Some(action) = action {
+                        return self.perform(action);
+                    }
+                }
+                Esc => self.overlay = None,
+                _ => {}
+            },
+            Overlay::JumpPercent { input } => match key.code {
+                Char(c @ '0'..='9') if input.len() < 3 => input.push(c),
+                Backspace => {
+                    input.pop();
+                }
+                Enter => {
+                    let percent = input.parse().ok();
+                    self.overlay = None;
+                    if let Some(percent) = percent {
+                        self.jump_to_percent(percent);
+                    }
+                }
+                Esc => self.overlay = None,
+                _ => {}
+            },
+            Overlay::ConfirmDelete { rowid, .. } => match key.code {
+                Char('y') => {
+                    let rowid = *rowid;
+                    self.overlay = None;
+                    self.delete_entry(rowid);
+                }
+                Char('n') | Esc => self.overlay = None,
+                _ => {}
+            },
+            #[cfg(feature = "leitner")]
+            Overlay::ConfirmAddToLeitner { .. } => match key.code {
+                Char('y') => {
+                    self.overlay = None;
+                    if self.add_to_leitner() {
+                        self.show_in_leitner();
+                    }
+                }
+                Char('n') | Esc => self.overlay = None,
+                _ => {}
+            },
+            Overlay::NewEntry {
+                step,
+                word,
+                definition,
+                rowid,
+            } => match (*step, key.code) {
+                (_, Esc) | (EntryStep::Overwrite, Char('n')) => self.overlay = None,
+                (EntryStep::Overwrite, Char('y')) => {
+                    let (word, definition) = (std::mem::take(word), std::mem::take(definition));
+                    self.overlay = None;
+                    self.save_entry(&word, &definition, true);
+                }
+                (EntryStep::Overwrite, _) => {}
+                (EntryStep::Word, Char(c)) => word.push(c),
+                (EntryStep::Word, Backspace) => {
+                    word.pop();
+                }
+                (EntryStep::Word, Enter) if !word.trim().is_empty() => {
+                    *step = EntryStep::Definition
+                }
+                (EntryStep::Definition, Char(c)) => definition.push(c),
+                (EntryStep::Definition, Backspace) => {
+                    definition.pop();
+                }
+                (EntryStep::Definition, Enter) => {
+                    if let Some(rowid) = *rowid {
+                        let definition = std::mem::take(definition);
+                        self.overlay = None;
+                        self.save_definition(rowid, &definition);
+                    } else if self.dictionary.contains(word.trim()).unwrap_or(false) {
+                        *step = EntryStep::Overwrite;
+                    } else {
+                        let (word, definition) = (std::mem::take(word), std::mem::take(definition));
+                        self.overlay = None;
+                        self.save_entry(&word, &definition, false);
+                    }
+                }
+                _ => {}
+            },
+        }
+        false
+    }
+
+    /// Actions of the current mode for the command palette, with their keys,
+    /// whose description fuzzily matches `query`.
+    fn palette_entries(&self, query: &str) -> Vec<(Action, String)> {
+        self.keymap
+            .actions(self.contexts())
+            .into_iter()
+            .filter(|(action, _)| *action != Action::CommandPalette)
+            .filter(|(action, _)| search::fuzzy_matches(action.description(), query))
+            .collect()
+    }
+
+    /// Adds the selected entry to Leitner unless it is already there. Returns
+    /// whether the word has a card.
+    #[cfg(feature = "leitner")]
+    fn add_to_leitner(&mut self) -> bool {
+        let mut entry = self.query_db_by_index(self.selected_index);
+        if self.current_dictionary().ansi {
+            entry.definition = ansi::strip(&entry.definition);
+        }
+        match self.leitner.add(&entry.word, &entry.definition) {
+            Ok(added) => {
+                let message = match added {
+                    true => format!("Added '{}' to Leitner", entry.word),
+                    false => format!("'{}' is already in Leitner", entry.word),
+                };
+                self.status = Some((message, Instant::now()));
+                true
+            }
+            Err(e) => {
+                self.definition = format!("Could not add the card: {}", e);
+                self.scroll = 0;
+                false
+            }
+        }
+    }
+
+    /// Switches to Leitner mode on the card of the selected word, or offers to
+    /// add it when there is none.
+    #[cfg(feature = "leitner")]
+    fn show_in_leitner(&mut self) {
+        let Some(word) = self.word_index.get(self.selected_index).cloned() else {
+            return;
+        };
+        match self.leitner.word_index.iter().position(|w| *w == word) {
+            Some(i) => {
+                self.mode = Mode::Leitner;
+                self.leitner.selected_index = i;
+                self.definition = self.leitner.get_definition(i);
+                self.scroll = 0;
+            }
+            None => self.overlay = Some(Overlay::ConfirmAddToLeitner { word }),
+        }
+    }
+
+    /// Deletes an entry and keeps the selection on the entry that took its place.
+    fn delete_entry(&mut self, rowid: i64) {
+        if let Err(e) = self.dictionary.delete(rowid) {
+            self.definition = format!("Could not delete the entry: {}", e);
+            self.scroll = 0;
+            return;
+        }
+        let index = self.selected_index;
+        self.create(self.database_path.clone());
+        self.finish_loading();
+        if !self.word_index.is_empty() {
+            self.select_index(min(index, self.word_index.len() - 1));
+        }
+    }
+
+    /// Inserts `word` into the current dictionary, or replaces the definition
+    /// of an existing entry when `overwrite` is set, then selects it.
+    fn save_entry(&mut self, word: &str, definition: &str, overwrite: bool) {
+        let word = word.trim();
+        let result = if overwrite {
+            self.dictionary.replace_definition(word, definition)
+        } else {
+            self.dictionary.insert(word, definition)
+        };
+        if let Err(e) = result {
+            self.definition = format!("Could not save '{}': {}", word, e);
+            self.scroll = 0;
+            return;
+        }
+        self.create(self.database_path.clone());
+        self.finish_loading();
+        // A new entry comes last among those with its headword.
+        if let Some(index) = self.word_index.iter().rposition(|w| w == word) {
+            self.select_index(index);
+        }
+    }
+
+    /// Replaces the definition of the entry with this ROWID only, leaving
+    /// other entries with the same headword alone, then selects it.
+    fn save_definition(&mut self, rowid: i64, definition: &str) {
+        if let Err(e) = self.dictionary.set_definition(rowid, definition) {
+            self.definition = format!("Could not save the entry: {}", e);
+            self.scroll = 0;
+            return;
+        }
+        self.create(self.database_path.clone());
+        self.finish_loading();
+        if let Ok(index) = self.rowids.binary_search(&rowid) {
+            self.select_index(index);
+        }
+    }
+
+    fn query_db(&mut self, word: String) {
+        if self.full_text_mode {
+            self.full_text_search();
+            return;
+        }
+        self.find(word, true);
+    }
+
+    /// Has the index and the live search follow a typed edit of the input
+    /// once typing pauses for `live_search_delay`.
+    fn input_typed(&mut self) {
+        self.input_edited = Some(Instant::now());
+        if self.config.ui.live_search_delay.is_zero() {
+            self.follow_input();
+        }
+    }
+
+    /// Updates the index and the live search for a typed edit.
+    fn follow_input(&mut self) {
+        self.input_edited = None;
+        self.on_input_changed();
+        self.search_as_you_type();
+    }
+
+    /// Looks up the input after an edit when live search is on. Inputs that
+    /// match nothing get no suggestions, as they are often unfinished.
+    fn search_as_you_type(&mut self) {
+        if !self.live_search || self.input.is_empty() || self.input == self.live_query {
+            return;
+        }
+        self.live_query = self.input.clone();
+        if self.full_text_mode {
+            self.full_text_search();
+            return;
+        }
+        self.find(self.input.clone(), false);
+    }
+
+    /// Selects the first headword matching `word`, or else, with `suggest`,
+    /// the closest ones to it. Searching for the same `word` again looks up
+    /// the selected suggestion.
+    fn find(&mut self, word: String, suggest: bool) {
+        self.stemmed_from = None;
+        self.finish_loading();
+        if let Some((query, suggestions)) = self.suggestions.take() {
+            if suggest && query == word {
+                let chosen = suggestions[self.suggestion].clone();
+                self.input.clone_from(&chosen);
+                self.on_input_changed();
+                if let Some(index) = self.word_index.iter().position(|w| *w == chosen) {
+                    self.select_index(index);
+                }
+                return;
+            }
+        }
+        if self.search_mode == SearchMode::Regex {
+            if let Err(e) = search::word_regex(&word) {
+                if suggest {
+                    let message = format!("Invalid regular expression: {}", e);
+                    self.status = Some((message, Instant::now()));
+                }
+                return;
+            }
+        }
+        if self.search_mode.lists_matches(&word) && self.filter.is_none() {
+            // Ranked candidates and pattern matches are listed in the index.
+            self.filter_kind = FilterKind::Input;
+            self.apply_filter();
+        }
+        let pick = match self.current_dictionary().index_order {
+            true => search::first_match,
+            false => search::best_match,
+        };
+        let whole_word = self.whole_word;
+        let (words, query, mode) = self.search_words(&word);
+        let found = pick(words, &query, mode, whole_word);
+        // On Enter, a miss falls back to the headword of an inflected form,
+        // and then to the closest headwords.
+        let plain = matches!(
+            self.search_mode,
+            SearchMode::Prefix | SearchMode::Contains | SearchMode::Exact
+        ) && !self.search_mode.is_glob(&query);
+        let stemmed = match (found, self.current_dictionary().stemmer) {
+            (None, Some(algorithm)) if suggest && plain => {
+                search::stem_match(&self.word_index_folded, &query, algorithm)
+            }
+            _ => None,
+        };
+        let closest = match found.or(stemmed) {
+            None if suggest
+                && !matches!(self.search_mode, SearchMode::Regex | SearchMode::Anagram) =>
+            {
+                search::closest_matches(&self.word_index_folded, &query, SUGGESTIONS)
+            }
+            _ => Vec::new(),
+        };
+        match found.or(stemmed).or(closest.first().copied()) {
+            Some(index) => {
+                self.selected_index = index;
+                self.sync_filter_cursor();
+                let entry = self.query_db_by_index(index);
+                self.show_entry(entry);
+                if stemmed.is_some() {
+                    self.stemmed_from = Some(word.clone());
+                }
+                if !closest.is_empty() {
+                    let words = closest.iter().map(|&i| self.word_index[i].clone());
+                    self.suggestions = Some((word, words.collect()));
+                    self.suggestion = 0;
+                }
+            }
+            None if self.config.ui.keep_definition => {
+                self.status = Some((format!("No match for '{}'", word), Instant::now()));
+                return;
+            }
+            None => {
+                self.definition = "Not found!".to_string();
+                self.see_also.clear();
+            }
+        }
+        self.scroll = 0;
+    }
+
+    /// The folded headwords to match `input` against, with `input` as matched
+    /// and the mode to match it in. Anagram searches look for the sorted
+    /// letters of `input` among those of the headwords, which are computed
+    /// once for the index and compared exactly.
+    fn search_words(&mut self, input: &str) -> (&[String], String, SearchMode) {
+        if self.search_mode != SearchMode::Anagram {
+            let query = self.search_mode.fold(input);
+            return (&self.word_index_folded, query, self.search_mode);
+        }
+        let keys = self.word_index_folded[self.anagram_keys.len()..]
+            .iter()
+            .map(|word| search::anagram_key(word));
+        self.anagram_keys.extend(keys.collect::<Vec<_>>());
+        let query = search::anagram_key(input);
+        if query.is_empty() {
+            return (&[], query, SearchMode::Exact);
+        }
+        (&self.anagram_keys, query, SearchMode::Exact)
+    }
+
+    /// The entry at `index`, with the definitions of all entries sharing its
+    /// headword merged into it.
+    fn query_db_by_index(&mut self, index: usize) -> Entry {
+        self.scroll = 0;
+        self.expanded = false;
+        let entry = self
+            .rowids
+            .get(index)
+            .and_then(|&rowid| self.dictionary.entry(rowid).ok().flatten());
+        let Some(mut entry) = entry else {
+            return Entry {
+                definition: "Not found!".to_string(),
+                ..Entry::default()
+            };
+        };
+        if let Some(rowids) = self.duplicates.get(&entry.word) {
+            let senses: Vec<String> = rowids
+                .iter()
+                .filter_map(|&rowid| self.dictionary.entry(rowid).ok().flatten())
+                .map(|sense| sense.definition)
+                .collect();
+            let ui = &self.config.ui;
+            entry.definition =
+                dictionary::merge_senses(&senses, &ui.sense_separator, ui.number_senses);
+        }
+        if entry.definition.trim().is_empty() {
+            entry.definition = NO_DEFINITION.to_string();
+        }
+        entry
+    }
+
+    fn run(&mut self, mut terminal: Terminal<impl Backend>) -> io::Result<()> {
+        loop {
+            if self.terminate.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            self.draw(&mut terminal)?;
+            // Keep reading the index between frames while it loads.
+            let mut timeout = match self.index_loaded() {
+                true => TICK_RATE,
+                false => Duration::ZERO,
+            };
+            if let Some(edited) = self.input_edited {
+                let delay = self.config.ui.live_search_delay;
+                timeout = timeout.min(delay.saturating_sub(edited.elapsed()));
+            }
+            if !event::poll(timeout)? {
+                if self.tick() {
+                    return Ok(());
+                }
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    self.handle_mouse(mouse);
+                    continue;
+                }
+                _ => continue,
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(recording) = &mut self.recording {
+                recording.push(key);
+            }
+            if self.handle_key(key) {
+                return Ok(());
+            }
+            if std::mem::take(&mut self.edit_requested) {
+                if let Err(e) = self.edit_definition(&mut terminal) {
+                    self.definition = format!("Could not edit the definition: {}", e);
+                    self.scroll = 0;
+                }
+            }
+        }
+    }
+
+    /// Opens the selected entry's definition in `$EDITOR`, one line per line
+    /// break, and saves the edited text back to the dictionary with its line
+    /// separator.
+    fn edit_definition<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        let to_io = |e: rusqlite::Error| io::Error::other(e);
+        let Some(&rowid) = self.rowids.get(self.selected_index) else {
+            return Ok(());
+        };
+        let newlines = self.dictionary.newlines();
+        let original = self
+            .dictionary
+            .raw_definition(rowid)
+            .map_err(to_io)?
+            .unwrap_or_default();
+        let original = newlines.apply(&original);
+        let path = std::env::temp_dir().join(format!("dicrs-{}.txt", std::process::id()));
+        fs::write(&path, &original)?;
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or("vi");
+
+        disable_raw_mode()?;
+        stdout().execute(DisableMouseCapture)?;
+        stdout().execute(LeaveAlternateScreen)?;
+        let status = std::process::Command::new(program)
+            .args(parts)
+            .arg(&path)
+            .status();
+        stdout().execute(EnterAlternateScreen)?;
+        if self.config.ui.mouse {
+            stdout().execute(EnableMouseCapture)?;
+        }
+        enable_raw_mode()?;
+        terminal.clear()?;
+
+        let edited = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+        if !status?.success() {
+            return Err(io::Error::other("the editor exited with an error"));
+        }
+        let edited = edited?.replace("\r\n", "\n").trim_end().to_string();
+        if edited != original.trim_end() {
+            self.dictionary
+                .set_definition(rowid, &newlines.restore(&edited))
+                .map_err(to_io)?;
+        }
+        let entry = self.query_db_by_index(self.selected_index);
+        self.show_entry(entry);
+        Ok(())
+    }
+
+    /// Looks up the definition word under a left click, copies it on a right
+    /// click, and scrolls the definition with the wheel.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.overlay.is_some() || self.databases.is_empty() {
+            return;
+        }
+        #[cfg(feature = "leitner")]
+        if self.mode == Mode::Leitner {
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.scroll = self.scroll.saturating_add(1),
+            MouseEventKind::ScrollUp => self.scroll = self.scroll.saturating_sub(1),
+            MouseEventKind::Down(button) => {
+                let Some(word) =
+                    word_at(&self.screen, self.definition_area, mouse.column, mouse.row)
+                else {
+                    return;
+                };
+                match button {
+                    MouseButton::Left => {
+                        self.input = word;
+                        self.on_input_changed();
+                        self.query_db(self.input.clone());
+                    }
+                    #[cfg(feature = "clipboard")]
+                    MouseButton::Right => {
+                        if let Some(ref mut cb) = self.clipboard {
+                            let _ = cb.set_contents(word);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs periodic work between key presses.
+    fn tick(&mut self) -> bool {
+        self.load_index_chunk();
+        if self
+            .input_edited
+            .is_some_and(|edited| edited.elapsed() >= self.config.ui.live_search_delay)
+        {
+            self.follow_input();
+        }
+        if !self.pending_keys.is_empty() && self.pending_since.elapsed() >= SEQUENCE_TIMEOUT {
+            let keys = std::mem::take(&mut self.pending_keys);
+            if let Resolution::Pending(Some(action)) = self.keymap.resolve(self.contexts(), &keys) {
+                return match self.count.take() {
+                    Some(count) => self.perform_counted(action, count),
+                    None => self.perform(action),
+                };
+            }
+            self.type_keys(&keys);
+        }
+        #[cfg(feature = "leitner")]
+        if let Err(e) = self.leitner.flush_if_due() {
+            self.definition = format!("Could not save the Leitner reviews: {}", e);
+            self.scroll = 0;
+        }
+        if let Some((_, since)) = &self.status {
+            if since.elapsed() >= STATUS_TIMEOUT {
+                self.status = None;
+            }
+        }
+        if let Some(child) = &mut self.speech {
+            match child.try_wait() {
+                Ok(None) => {}
+                Ok(Some(status)) => {
+                    if !status.success() {
+                        self.definition = format!("The speech command failed ({})", status);
+                        self.scroll = 0;
+                    }
+                    self.speech = None;
+                }
+                Err(_) => self.speech = None,
+            }
+        }
+        false
+    }
+
+    /// Runs the configured text-to-speech command on the current headword
+    /// without waiting for it to finish.
+    fn pronounce(&mut self) {
+        #[cfg(feature = "leitner")]
+        let word = if self.mode == Mode::Leitner {
+            self.leitner.word_index.get(self.leitner.selected_index)
+        } else {
+            self.word_index.get(self.selected_index)
+        };
+        #[cfg(not(feature = "leitner"))]
+        let word = self.word_index.get(self.selected_index);
+        let Some(word) = word.cloned() else {
+            return;
+        };
+        let Some(template) = &self.config.speech_command else {
+            self.definition =
+                "No speech command configured. Set `command` under [speech] in the config."
+                    .to_string();
+            self.scroll = 0;
+            return;
+        };
+        let mut args = template
+            .split_whitespace()
+            .map(|arg| arg.replace("%w", &word));
+        let Some(program) = args.next() else {
+            return;
+        };
+        let spawned = std::process::Command::new(&program)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(child) => self.speech = Some(child),
+            Err(e) => {
+                self.definition = format!("Could not run '{}': {}", program, e);
+                self.scroll = 0;
+            }
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(mut keys) => {
+                // Drop the key that stopped the recording.
+                keys.pop();
+                self.macro_keys = keys;
+            }
+            None => self.recording = Some(Vec::new()),
+        }
+    }
+
+    fn play_macro(&mut self) -> bool {
+        if let Some(recording) = &mut self.recording {
+            recording.pop();
+            return false;
+        }
+        for key in self.macro_keys.clone() {
+            if self.handle_key(key) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn contexts(&self) -> &'static [Context] {
+        #[cfg(feature = "leitner")]
+        if self.mode == Mode::Leitner {
+            return &[Context::Leitner];
+        }
+        if self.normal_mode {
+            &[Context::Normal, Context::Browse]
+        } else {
+            &[Context::Browse]
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        use KeyCode::*;
+        if self.databases.is_empty() {
+            match (key.code, key.modifiers) {
+                (Char('c'), KeyModifiers::CONTROL) => return true,
+                (Char('r'), KeyModifiers::NONE) => self.rescan(),
+                _ => {}
+            }
+            return false;
+        }
+        if self.overlay.is_some() {
+            if (key.code, key.modifiers) == (Char('c'), KeyModifiers::CONTROL) {
+                return true;
+            }
+            return self.handle_overlay_key(key);
+        }
+        if let (Char(c @ '0'..='9'), KeyModifiers::NONE) = (key.code, key.modifiers) {
+            if self.normal_mode
+                && self.pending_keys.is_empty()
+                && (c != '0' || self.count.is_some())
+            {
+                let digit = c.to_digit(10).unwrap() as usize;
+                self.count = Some(min(self.count.unwrap_or(0) * 10 + digit, 1_000_000));
+                return false;
+            }
+        }
+        self.pending_keys.push(Key::from(key));
+        self.pending_since = Instant::now();
+        match self.keymap.resolve(self.contexts(), &self.pending_keys) {
+            Resolution::Action(action) => {
+                self.pending_keys.clear();
+                match self.count.take() {
+                    Some(count) => self.perform_counted(action, count),
+                    None => self.perform(action),
+                }
+            }
+            Resolution::Pending(_) => false,
+            Resolution::Unbound => {
+                let mut keys = std::mem::take(&mut self.pending_keys);
+                self.count = None;
+                if keys.len() > 1 {
+                    // The sequence did not complete: run what the keys typed so far
+                    // mean on their own, then handle the new key by itself.
+                    keys.pop();
+                    match self.keymap.resolve(self.contexts(), &keys) {
+                        Resolution::Pending(Some(action)) | Resolution::Action(action) => {
+                            if self.perform(action) {
+                                return true;
+                            }
+                        }
+                        _ => self.type_keys(&keys),
+                    }
+                    return self.handle_key(key);
+                }
+                self.type_keys(&keys);
+                false
+            }
+        }
+    }
+
+    /// Types the characters of `keys` into the input while browsing, for keys
+    /// that are bound to nothing on their own.
+    fn type_keys(&mut self, keys: &[Key]) {
+        if self.contexts() != [Context::Browse] {
+            return;
+        }
+        for key in keys {
+            if let KeyCode::Char(c) = key.code {
+                self.input.push(c);
+                self.input_typed();
+            }
+        }
+    }
+
+    /// Writes the selected entry, with its styling, as an HTML snippet into
+    /// the data folder and optionally copies it to the clipboard.
+    fn export_html(&mut self) {
+        let entry = self.query_db_by_index(self.selected_index);
+        if entry.word.is_empty() {
+            return;
+        }
+        let text = if self.current_dictionary().ansi {
+            ansi::to_text(&entry.definition)
+        } else {
+            Text::from(entry.definition)
+        };
+        let html = html::from_text(&entry.word, &text);
+        let name: String = entry
+            .word
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let dir = self.datapath.join("html");
+        let path = dir.join(format!("{}.html", name));
+        let result = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, &html));
+        self.definition = match result {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(e) => format!("Could not write {}: {}", path.display(), e),
+        };
+        self.scroll = 0;
+        #[cfg(feature = "clipboard")]
+        if self.config.html_to_clipboard {
+            if let Some(ref mut cb) = self.clipboard {
+                if cb.set_contents(html).is_ok() {
+                    self.definition.push_str(" and copied it to the clipboard");
+                }
+            }
+        }
+    }
+
+    /// Writes the favorites of the current dictionary, in index order, with
+    /// their definitions to a study sheet in the data folder.
+    fn export_favorites(&mut self) {
+        let name = self.databases[self.dictionary_index].clone();
+        let favorites = match self.favorites.words(&name) {
+            Ok(favorites) => favorites,
+            Err(e) => {
+                self.definition = format!("Could not read the favorites: {}", e);
+                self.scroll = 0;
+                return;
+            }
+        };
+        self.finish_loading();
+        let mut entries = Vec::new();
+        for index in 0..self.word_index.len() {
+            let word = &self.word_index[index];
+            if !favorites.contains(word) || entries.iter().any(|(w, _)| w == word) {
+                continue;
+            }
+            let mut entry = self.query_db_by_index(index);
+            if self.current_dictionary().ansi {
+                entry.definition = ansi::strip(&entry.definition);
+            }
+            entries.push((entry.word, entry.definition));
+        }
+        let format = self.config.sheet_format;
+        let sheet = favorites::study_sheet(
+            &format!("Favorites: {}", name),
+            &entries,
+            format,
+            self.config.sheet_headings,
+        );
+        let path = self
+            .datapath
+            .join(format!("favorites-{}.{}", name, format.extension()));
+        self.definition = match fs::write(&path, sheet) {
+            Ok(()) => format!("Wrote {} favorites to {}", entries.len(), path.display()),
+            Err(e) => format!("Could not write {}: {}", path.display(), e),
+        };
+        self.scroll = 0;
+    }
+
+    /// Version, build and folder details for bug reports.
+    fn about(&self) -> Vec<(String, String)> {
+        let features: Vec<&str> = [
+            ("leitner", cfg!(feature = "leitner")),
+            ("clipboard", cfg!(feature = "clipboard")),
+        ]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect();
+        let build = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        [
+            ("Version", env!("CARGO_PKG_VERSION").to_string()),
+            (
+                "Build",
+                format!(
+                    "{} ({}-{})",
+                    build,
+                    std::env::consts::ARCH,
+                    std::env::consts::OS
+                ),
+            ),
+            (
+                "Features",
+                if features.is_empty() {
+                    "none".to_string()
+                } else {
+                    features.join(", ")
+                },
+            ),
+            ("Data folder", self.datapath.display().to_string()),
+            ("Dictionaries", self.dicpath.display().to_string()),
+        ]
+        .into_iter()
+        .map(|(label, value)| (label.to_string(), value))
+        .collect()
+    }
+
+    /// The selected entry's definition exactly as stored in the dictionary.
+    fn raw_definition(&self) -> Option<String> {
+        let rowid = self.rowids.get(self.selected_index)?;
+        self.dictionary.raw_definition(*rowid).ok().flatten()
+    }
+
+    fn read_only_message(&self) -> String {
+        let name = &self.databases[self.dictionary_index];
+        format!(
+            "{} is read-only. Set `writable = true` under [dictionaries.{}] in the config to edit it.",
+            name,
+            config::quote(name)
+        )
+    }
+
+    fn perform(&mut self, action: Action) -> bool {
+        // Other actions see the index as it is for the current input.
+        if self.input_edited.is_some() && !matches!(action, Action::DeleteChar | Action::DeleteWord)
+        {
+            self.follow_input();
+        }
+        // Where these leave from and land is remembered for going back.
+        let navigates = matches!(
+            action,
+            Action::Search
+                | Action::FollowLink
+                | Action::PreviousDictionary
+                | Action::NextDictionary
+                | Action::AlternateDictionary
+                | Action::RecentDictionary
+                | Action::PreviousDictionaryWithWord
+                | Action::NextDictionaryWithWord
+        );
+        if navigates {
+            self.remember_visit();
+        }
+        match action {
+            Action::Quit => return true,
+            #[cfg(feature = "clipboard")]
+            Action::CopyDefinition => {
+                let definition = if self.current_dictionary().ansi {
+                    ansi::strip(&self.definition)
+                } else {
+                    self.definition.to_owned()
+                };
+                if let Some(ref mut cb) = self.clipboard {
+                    cb.set_contents(definition).unwrap()
+                }
+            }
+            #[cfg(feature = "clipboard")]
+            Action::CopyMarkdown => {
+                let Some(word) = self.word_index.get(self.selected_index) else {
+                    return false;
+                };
+                let definition = if self.current_dictionary().ansi {
+                    ansi::strip(&self.definition)
+                } else {
+                    self.definition.to_owned()
+                };
+                let snippet = markdown_quote(word, &definition);
+                if let Some(ref mut cb) = self.clipboard {
+                    cb.set_contents(snippet).unwrap()
+                }
+            }
+            Action::ToggleMinimal => {
+                self.mode = if self.mode != Mode::Minimal {
+                    Mode::Minimal
+                } else {
+                    Mode::Default
+                };
+                // Show the selected entry in both modes, keeping the scroll
+                // position when it is the one already shown.
+                let (definition, scroll, expanded) = (
+                    std::mem::take(&mut self.definition),
+                    self.scroll,
+                    self.expanded,
+                );
+                self.update_by_index(0);
+                if self.definition == definition {
+                    self.scroll = scroll;
+                    self.expanded = expanded;
+                }
+            }
+            Action::Search if self.full_text_mode => {
+                self.build_full_text();
+                self.full_text_search();
+            }
+            Action::Search if self.filter.is_some() && self.filter_kind == FilterKind::Reverse => {
+                if self.input.is_empty() {
+                    self.clear_filter();
+                } else {
+                    self.definition_pattern = self.reverse_pattern();
+                    self.apply_filter();
+                }
+            }
+            Action::Search
+                if self.filter.is_some() && self.filter_kind == FilterKind::Definitions =>
+            {
+                match Regex::new(&self.input) {
+                    Ok(pattern) => {
+                        self.definition_pattern = Some(pattern);
+                        self.apply_filter();
+                    }
+                    Err(e) => {
+                        self.definition = format!("Invalid pattern: {}", e);
+                        self.scroll = 0;
+                    }
+                }
+            }
+            Action::Search if self.filter.is_some() => self.apply_filter(),
+            Action::Search if self.search_all_mode => self.search_all(),
+            Action::Search => self.query_db(self.input.to_string()),
+            Action::SearchAll => self.search_all(),
+            Action::ToggleSearchAll => {
+                self.search_all_mode = !self.search_all_mode;
+                let state = if self.search_all_mode { "on" } else { "off" };
+                let message = format!("Searching all dictionaries {}", state);
+                self.status = Some((message, Instant::now()));
+            }
+            Action::ToggleFilter => self.toggle_filter(FilterKind::Input),
+            Action::SearchDefinitions => self.search_definitions(),
+            Action::ToggleFavorite => self.toggle_favorite(),
+            Action::FavoritesOnly => self.toggle_filter(FilterKind::Favorites),
+            Action::ReverseSearch => self.reverse_search(),
+            Action::ToggleFullText => self.toggle_full_text(),
+            Action::ExportFavorites => self.export_favorites(),
+            Action::Refresh => self.refresh(),
+            Action::ToggleRaw => self.show_raw = !self.show_raw,
+            Action::ToggleFold => {
+                self.expanded = !self.expanded;
+                self.scroll = 0;
+            }
+            Action::NextLink => self.next_link(),
+            Action::FollowLink => self.follow_link(),
+            Action::JumpPercent => {
+                self.overlay = Some(Overlay::JumpPercent {
+                    input: String::new(),
+                })
+            }
+            Action::ExportHtml => self.export_html(),
+            Action::Help => {
+                let context = self.contexts()[0];
+                let title = match context {
+                    Context::Browse => "Keys",
+                    Context::Normal => "Normal mode keys",
+                    #[cfg(feature = "leitner")]
+                    Context::Leitner => "Leitner keys",
+                };
+                self.overlay = Some(Overlay::Help {
+                    title,
+                    entries: self
+                        .keymap
+                        .help(context)
+                        .into_iter()
+                        .map(|(keys, description)| (keys, description.to_string()))
+                        .collect(),
+                });
+            }
+            Action::CommandPalette => {
+                self.overlay = Some(Overlay::Palette {
+                    input: String::new(),
+                    cursor: 0,
+                })
+            }
+            Action::About => {
+                self.overlay = Some(Overlay::Help {
+                    title: "About dicrs",
+                    entries: self.about(),
+                })
+            }
+            Action::Pronounce => self.pronounce(),
+            Action::CycleSearchMode => {
+                self.search_mode = self.search_mode.next();
+                self.on_input_changed();
+                self.save_state();
+            }
+            Action::ToggleWholeWord => {
+                self.whole_word = !self.whole_word;
+                self.on_input_changed();
+                if self.filter.is_some() && self.filter_kind == FilterKind::Reverse {
+                    self.definition_pattern = self.reverse_pattern();
+                    self.apply_filter();
+                }
+            }
+            Action::Validate => {
+                self.definition =
+                    match tools::validate(&self.database_path, self.current_dictionary()) {
+                        Ok(report) => format!(
+                            "Validation of {}\n\n{}",
+                            self.databases[self.dictionary_index], report
+                        ),
+                        Err(e) => format!("Validation failed: {}", e),
+                    };
+                self.scroll = 0;
+            }
+            Action::EditDefinition => {
+                if self.current_dictionary().writable {
+                    self.edit_requested = !self.rowids.is_empty();
+                } else {
+                    self.definition = self.read_only_message();
+                    self.scroll = 0;
+                }
+            }
+            Action::AddEntry => {
+                if self.current_dictionary().writable {
+                    self.overlay = Some(Overlay::NewEntry {
+                        step: EntryStep::Word,
+                        word: String::new(),
+                        definition: String::new(),
+                        rowid: None,
+                    });
+                } else {
+                    self.definition = self.read_only_message();
+                    self.scroll = 0;
+                }
+            }
+            Action::EditEntry | Action::DeleteEntry => {
+                if !self.current_dictionary().writable {
+                    self.definition = self.read_only_message();
+                    self.scroll = 0;
+                    return false;
+                }
+                let Some(&rowid) = self.rowids.get(self.selected_index) else {
+                    return false;
+                };
+                let Ok(Some(entry)) = self.dictionary.entry(rowid) else {
+                    return false;
+                };
+                self.overlay = Some(if action == Action::EditEntry {
+                    Overlay::NewEntry {
+                        step: EntryStep::Definition,
+                        word: entry.word,
+                        definition: entry.definition,
+                        rowid: Some(rowid),
+                    }
+                } else {
+                    Overlay::ConfirmDelete {
+                        rowid,
+                        word: entry.word,
+                    }
+                });
+            }
+            Action::Up => self.move_selection(-1),
+            Action::Down => self.move_selection(1),
+            Action::JumpUp => self.move_selection(-10),
+            Action::JumpDown => self.move_selection(10),
+            Action::Top => self.move_selection(-(self.word_index.len() as isize)),
+            Action::Bottom => {
+                self.finish_loading();
+                self.move_selection(self.word_index.len() as isize)
+            }
+            Action::PreviousDictionary => {
+                self.change_database(-1);
+                self.query_db(self.input.to_string());
+            }
+            Action::NextDictionary => {
+                self.change_database(1);
+                self.query_db(self.input.to_string());
+            }
+            Action::AlternateDictionary => {
+                if self.previous_dictionary_index < self.databases.len() {
+                    self.change_database(
+                        self.previous_dictionary_index as isize - self.dictionary_index as isize,
+                    );
+                    self.query_db(self.input.to_string());
+                }
+            }
+            Action::RecentDictionary => self.cycle_recent_dictionary(),
+            Action::Back => self.step_history(-1),
+            Action::Forward => self.step_history(1),
+            Action::PreviousDictionaryWithWord => self.dictionary_with_word(-1),
+            Action::NextDictionaryWithWord => self.dictionary_with_word(1),
+            Action::ScrollUp => self.scroll = self.scroll.saturating_sub(1),
+            Action::ScrollDown => self.scroll += 1,
+            Action::DeleteChar | Action::DeleteWord
+                if self.input.is_empty()
+                    && self.filter.is_some()
+                    && self.config.ui.backspace_escapes =>
+            {
+                self.clear_filter()
+            }
+            Action::DeleteChar => {
+                self.input.pop();
+                self.input_typed();
+            }
+            Action::DeleteWord => {
+                delete_last_word(&mut self.input);
+                self.input_typed();
+            }
+            Action::ToggleLiveSearch => {
+                self.live_search = !self.live_search;
+                let state = if self.live_search { "on" } else { "off" };
+                self.status = Some((format!("Live search {}", state), Instant::now()));
+            }
+            Action::NormalMode => self.normal_mode = true,
+            Action::InsertMode => self.normal_mode = false,
+            Action::RecordMacro => self.toggle_recording(),
+            Action::PlayMacro => return self.play_macro(),
+            #[cfg(feature = "leitner")]
+            Action::EnterLeitner => {
+                self.mode = Mode::Leitner;
+                self.leitner.next();
+                self.definition = if self.leitner.due_count() == 0 {
+                    "All caught up! No more cards are due today.".to_string()
+                } else {
+                    String::new()
+                };
+                self.perform(Action::Help);
+            }
+            #[cfg(feature = "leitner")]
+            Action::AddToLeitner => {
+                self.add_to_leitner();
+            }
+            #[cfg(feature = "leitner")]
+            Action::ShowInLeitner => self.show_in_leitner(),
+            #[cfg(feature = "leitner")]
+            Action::ReviewCorrect | Action::ReviewIncorrect => {
+                let result = self.leitner.review(action == Action::ReviewCorrect);
+                if let Ok(leitner::ReviewOutcome::NotDue(date)) = result {
+                    self.definition = format!(
+                        "This card is not due until {} ({}).",
+                        date.format("%Y-%m-%d"),
+                        leitner::get_relative_date(
+                            date,
+                            chrono::Local::now().date_naive(),
+                            &self.config.leitner
+                        )
+                    );
+                    self.scroll = 0;
+                } else if result.is_ok() {
+                    self.leitner.next();
+                    self.definition = if self.leitner.due_count() == 0 {
+                        "All caught up! No more cards are due today.".to_string()
+                    } else {
+                        String::new()
+                    };
+                    self.scroll = 0;
+                }
+            }
+            #[cfg(feature = "leitner")]
+            Action::ShowCardDefinition => {
+                self.definition = self.leitner.get_definition(self.leitner.selected_index);
+            }
+            #[cfg(feature = "leitner")]
+            Action::ExportAnki => {
+                let path = self.datapath.join("leitner-anki.tsv");
+                let tags = self.config.leitner.anki_schedule_tags;
+                self.definition = match self.leitner.export_anki(&path, tags) {
+                    Ok(count) => format!("Exported {} cards to {}", count, path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+                self.scroll = 0;
+            }
+            #[cfg(feature = "leitner")]
+            Action::LeaveLeitner => {
+                self.mode = Mode::Default;
+                self.update_by_index(0);
+            }
+            #[cfg(feature = "leitner")]
+            Action::LeitnerToMinimal => {
+                self.mode = Mode::Minimal;
+                self.update_by_index(0);
+            }
+        }
+        if navigates {
+            self.remember_visit();
+        }
+        false
+    }
+
+    /// Runs a motion `count` times; other actions ignore the count.
+    fn perform_counted(&mut self, action: Action, count: usize) -> bool {
+        let count = count as isize;
+        match action {
+            Action::Up => self.move_selection(-count),
+            Action::Down => self.move_selection(count),
+            Action::JumpUp => self.move_selection(-10 * count),
+            Action::JumpDown => self.move_selection(10 * count),
+            Action::JumpPercent => self.jump_to_percent(count as usize),
+            // Like `5gg` in vim: go to the given entry.
+            Action::Top | Action::Bottom => {
+                self.move_selection(-(self.word_index.len() as isize));
+                self.move_selection(count - 1);
+            }
+            Action::PreviousDictionary
+            | Action::NextDictionary
+            | Action::ScrollUp
+            | Action::ScrollDown => {
+                for _ in 0..count {
+                    self.perform(action);
+                }
+            }
+            _ => return self.perform(action),
+        }
+        false
+    }
+
+    /// Selects the entry `percent` percent of the way through the index.
+    fn jump_to_percent(&mut self, percent: usize) {
+        self.finish_loading();
+        #[cfg(feature = "leitner")]
+        let len = match self.mode {
+            Mode::Leitner => self.leitner.word_index.len(),
+            _ => self.filter.as_ref().map_or(self.word_index.len(), Vec::len),
+        };
+        #[cfg(not(feature = "leitner"))]
+        let len = self.filter.as_ref().map_or(self.word_index.len(), Vec::len);
+        let target = len * percent.min(100) / 100;
+        self.move_selection(-(len as isize));
+        self.move_selection(target.min(len.saturating_sub(1)) as isize);
+    }
+
+    fn move_selection(&mut self, i: isize) {
+        #[cfg(feature = "leitner")]
+        if self.mode == Mode::Leitner {
+            self.leitner.update_index_by(i as i32);
+            return;
+        }
+        self.update_by_index(i);
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal<impl Backend>) -> io::Result<()> {
+        let frame = terminal.draw(|f| ui(f, self))?;
+        if self.config.ui.mouse {
+            self.screen.clone_from(frame.buffer);
+        }
+        Ok(())
+    }
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let area = f.area();
+    app.definition_area = Rect::default();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        let message = Paragraph::new(format!(
+            "Terminal too small: {}x{}. Please resize it to at least {}x{}.",
+            area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+        ))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+    } else if app.databases.is_empty() {
+        render_empty_state(f, app);
+    } else {
+        match app.mode {
+            Mode::Default => render_default_mode(f, app),
+            Mode::Minimal => render_minimal_mode(f, app),
+            #[cfg(feature = "leitner")]
+            Mode::Leitner => render_leitner_mode(f, app),
+        }
+        if let Some(overlay) = &app.overlay {
+            render_overlay(f, app, overlay);
+        }
+    }
+    if app.config.theme.monochrome {
+        // Strip colors after rendering so every widget, including ANSI
+        // definitions, is covered; modifiers are kept for emphasis.
+        for cell in f.buffer_mut().content.iter_mut() {
+            cell.set_fg(Color::Reset).set_bg(Color::Reset);
+        }
+    }
+}
+
+fn render_overlay(f: &mut Frame, app: &App, overlay: &Overlay) {
+    let area = centered_rect(f.area(), 60, 70);
+    f.render_widget(Clear, area);
+    match overlay {
+        Overlay::Results { hits, cursor } => {
+            let mut items = Vec::new();
+            let mut selected = None;
+            for (i, hit) in hits.iter().enumerate() {
+                if i == 0 || hits[i - 1].dictionary_index != hit.dictionary_index {
+                    items.push(ListItem::new(Line::from(
+                        app.databases[hit.dictionary_index].as_str().bold(),
+                    )));
+                }
+                if i == *cursor {
+                    selected = Some(items.len());
+                }
+                items.push(ListItem::new(format!("  {}", hit.word)));
+            }
+            if hits.is_empty() {
+                items.push(ListItem::new("No matches in any dictionary."));
+            }
+            let results = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("All dictionaries: {}", app.input))
+                        .title_bottom("Enter: open  Esc: close"),
+                )
+                .highlight_style(app.config.theme.highlight);
+            let mut state = ListState::default().with_selected(selected);
+            f.render_stateful_widget(results, area, &mut state);
+        }
+        Overlay::Help { title, entries } => {
+            let width = entries
+                .iter()
+                .map(|(keys, _)| keys.chars().count())
+                .max()
+                .unwrap_or(0);
+            let lines: Vec<Line> = entries
+                .iter()
+                .map(|(keys, description)| {
+                    Line::from(vec![
+                        format!("{:width$}  ", keys, width = width).bold(),
+                        description.as_str().into(),
+                    ])
+                })
+                .collect();
+            let help = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(*title)
+                    .title_bottom("Press any key to close"),
+            );
+            f.render_widget(help, area);
+        }
+        Overlay::JumpPercent { input } => {
+            let prompt = Paragraph::new(format!("{}%", input)).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Jump to")
+                    .title_bottom("Enter: jump  Esc: cancel"),
+            );
+            f.render_widget(prompt, centered_rect(f.area(), 30, 20));
+        }
+        Overlay::Palette { input, cursor } => {
+            let entries = app.palette_entries(input);
+            let width = entries
+                .iter()
+                .map(|(_, keys)| keys.chars().count())
+                .max()
+                .unwrap_or(0);
+            let mut items: Vec<ListItem> = entries
+                .iter()
+                .map(|(action, keys)| {
+                    ListItem::new(Line::from(vec![
+                        format!("{:width$}  ", keys, width = width).bold(),
+                        action.description().into(),
+                    ]))
+                })
+                .collect();
+            if items.is_empty() {
+                items.push(ListItem::new("No matching actions."));
+            }
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("Commands: {}", input))
+                        .title_bottom("Enter: run  Esc: close"),
+                )
+                .highlight_style(app.config.theme.highlight);
+            let selected = (!entries.is_empty()).then_some(*cursor);
+            let mut state = ListState::default().with_selected(selected);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Overlay::ConfirmDelete { word, .. } => {
+            let prompt = Paragraph::new(format!("Delete '{}'?", word)).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Delete from {}",
+                        app.databases[app.dictionary_index]
+                    ))
+                    .title_bottom("y: delete  n: cancel"),
+            );
+            f.render_widget(prompt, centered_rect(f.area(), 40, 20));
+        }
+        #[cfg(feature = "leitner")]
+        Overlay::ConfirmAddToLeitner { word } => {
+            let prompt = Paragraph::new(format!("'{}' has no card yet. Add it?", word)).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Leitner")
+                    .title_bottom("y: add  n: cancel"),
+            );
+            f.render_widget(prompt, centered_rect(f.area(), 40, 20));
+        }
+        Overlay::NewEntry {
+            step,
+            word,
+            definition,
+            rowid,
+        } => {
+            let help = match step {
+                EntryStep::Word => "Enter: next  Esc: cancel",
+                EntryStep::Definition => "Enter: save  Esc: cancel",
+                EntryStep::Overwrite => "y: overwrite  n: cancel",
+            };
+            let mut lines = vec![
+                Line::from(vec!["Word: ".bold(), word.as_str().into()]),
+                Line::from(vec!["Definition: ".bold(), definition.as_str().into()]),
+            ];
+            if *step == EntryStep::Overwrite {
+                lines.push(Line::default());
+                lines.push(Line::from(format!(
+                    "'{}' already exists. Overwrite its definition?",
+                    word.trim()
+                )));
+            }
+            let prompt = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "{} in {}",
+                        if rowid.is_some() {
+                            "Edit entry"
+                        } else {
+                            "New entry"
+                        },
+                        app.databases[app.dictionary_index]
+                    ))
+                    .title_bottom(help),
+            );
+            f.render_widget(prompt, area);
+        }
+    }
+}
+
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::vertical([Constraint::Percentage(percent_y)]).flex(layout::Flex::Center);
+    let horizontal =
+        Layout::horizontal([Constraint::Percentage(percent_x)]).flex(layout::Flex::Center);
+    let [area] = vertical.areas(area);
+    let [area] = horizontal.areas(area);
+    area
+}
+
+fn input_title(app: &App) -> String {
+    let mut title = match app.full_text_mode {
+        true => "Input [full-text]".to_string(),
+        false => format!("Input [{}]", app.search_mode.name()),
+    };
+    let reverse = app.filter.is_some() && app.filter_kind == FilterKind::Reverse;
+    if app.whole_word && (app.search_mode == SearchMode::Contains || reverse) {
+        title.push_str(" [whole word]");
+    }
+    if app.search_all_mode {
+        title.push_str(" [all]");
+    }
+    if app.normal_mode {
+        match app.count {
+            Some(count) => title.push_str(&format!(" [normal {}]", count)),
+            None => title.push_str(" [normal]"),
+        }
+    }
+    if app.recording.is_some() {
+        title.push_str(" [rec]");
+    }
+    title
+}
+
+fn render_empty_state(f: &mut Frame, app: &App) {
+    let text = format!(
+        "No dictionaries found.\n\n\
+Copy one or more SQLite dictionaries ({ext} files) into:\n\
+    {path}\n\n\
+Each file needs a table named `dictionary` with `word` and `definition` text columns:\n\
+    CREATE TABLE dictionary (word TEXT, definition TEXT);\n\n\
+r: Rescan the dictionaries folder.\n\
+Ctrl + C: Exit.",
+        ext = app.config.extensions.join(", "),
+        path = app.dicpath.display(),
+    );
+    let help = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Welcome to dic.rs"),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(help, f.area());
+}
+
+fn render_default_mode(f: &mut Frame, app: &mut App) {
+    let vertical = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length((min(4, app.databases.len()) + 2) as u16),
+        Constraint::Min(5),
+    ]);
+    let [input_area, databases_area, rest_area] = vertical.areas(f.area());
+
+    let gauge_width = if app.config.ui.index_gauge { 1 } else { 0 };
+    let vertical = Layout::horizontal([
+        Constraint::Length(18),
+        Constraint::Length(gauge_width),
+        Constraint::Min(0),
+    ]);
+    let [words_area, gauge_area, definition_area] = vertical.areas(rest_area);
+
+    let input = Paragraph::new(app.input.as_str())
+        .style(Style::default().fg(Color::LightCyan))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Reset))
+                .title(input_title(app)),
+        );
+    f.render_widget(input, input_area);
+
+    let databases = List::new(app.databases.iter().map(|name| {
+        let style = match app.config.dictionary(name).color {
+            Some(color) => Style::default().fg(color),
+            None => Style::default(),
+        };
+        ListItem::new(name.as_str()).style(style)
+    }))
+    .block(Block::default().borders(Borders::ALL).title("Dictionaries"))
+    .highlight_style(
+        Style::default()
+            .fg(app.current_dictionary().color.unwrap_or(Color::Yellow))
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut state = ListState::default().with_selected(Some(app.dictionary_index));
+    f.render_stateful_widget(databases, databases_area, &mut state);
+
+    let (len, position) = match &app.filter {
+        Some(filter) => (filter.len(), app.filter_cursor),
+        None => (app.word_index.len(), app.selected_index),
+    };
+    let height = (words_area.height as usize).saturating_sub(2);
+    let before = max(position as isize - height as isize / 2, 0) as usize;
+    let after = min(position + height, len);
+    let word_index: Vec<&str> = match &app.filter {
+        Some(filter) => filter[before..after]
+            .iter()
+            .map(|&i| app.word_index[i].as_str())
+            .collect(),
+        None => app.word_index[before..after]
+            .iter()
+            .map(String::as_str)
+            .collect(),
+    };
+    let title = match (&app.filter, app.filter_kind) {
+        (None, _) if !app.index_loaded() => format!(
+            "Index [{}%]",
+            app.word_index.len() * 100 / app.index_total.max(1)
+        ),
+        (None, _) => "Index".to_string(),
+        (Some(_), FilterKind::Input) if app.search_mode.is_ranked(&app.input) => {
+            "Index [fuzzy]".to_string()
+        }
+        (Some(_), FilterKind::Input) => "Index [filter]".to_string(),
+        (Some(_), FilterKind::Favorites) => "Index [favorites]".to_string(),
+        (Some(_), FilterKind::Definitions) => "Index [definitions]".to_string(),
+        (Some(_), FilterKind::Reverse) => "Index [reverse]".to_string(),
+        (Some(_), FilterKind::FullText) => "Index [full-text]".to_string(),
+    };
+    let word_index = List::new(word_index)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_style(accent(app))
+                .title_bottom(match_count(position, app.filter.as_ref().map(Vec::len))),
+        )
+        .highlight_style(app.config.theme.highlight);
+    let mut state = ListState::default().with_selected(Some(min(position, height / 2)));
+    f.render_stateful_widget(word_index, words_area, &mut state);
+    if app.config.ui.index_gauge {
+        render_index_gauge(f, gauge_area, position, len);
+    }
+    let text = definition_text(app);
+    let definition_area = reader_area(definition_area, app.config.ui.max_width);
+    app.definition_area = definition_area;
+    let max_scroll = calculate_max_scroll(&text, definition_area.width, definition_area.height);
+    app.scroll = app.scroll.min(max_scroll);
+    let definition = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(definition_title(app))
+                .title_style(accent(app))
+                .title_bottom(status_line(app))
+                .title(scroll_position(app.scroll, max_scroll)),
+        )
+        .scroll((app.scroll, 0))
+        .wrap(Wrap { trim: true });
+    f.render_widget(definition, definition_area);
+    render_scrollbar(f, app, definition_area, max_scroll);
+}
+
+/// Draws a vertical bar filled in proportion to how far `position` is through
+/// `len` entries, aligned with the inside of the index pane's borders.
+fn render_index_gauge(f: &mut Frame, area: Rect, position: usize, len: usize) {
+    let rows = area.height.saturating_sub(2) as usize;
+    if rows == 0 || len == 0 {
+        return;
+    }
+    let filled = ((position + 1) * rows).div_ceil(len);
+    let lines: Vec<Line> = (0..rows)
+        .map(|row| {
+            if row < filled {
+                Line::styled("█", Style::default().fg(Color::DarkGray))
+            } else {
+                Line::styled("│", Style::default().fg(Color::DarkGray))
+            }
+        })
+        .collect();
+    let inner = Rect {
+        y: area.y + 1,
+        height: rows as u16,
+        ..area
+    };
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_minimal_mode(f: &mut Frame, app: &mut App) {
+    let vertical = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ]);
+    let [input_area, definition_area, status_area] = vertical.areas(f.area());
+
+    let input = Paragraph::new(app.input.as_str())
+        .style(Style::default().fg(Color::LightCyan))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Reset))
+                .title(input_title(app)),
+        );
+    f.render_widget(input, input_area);
+    let text = definition_text(app);
+    let definition_area = reader_area(definition_area, app.config.ui.max_width);
+    app.definition_area = definition_area;
+    let max_scroll = calculate_max_scroll(&text, definition_area.width, definition_area.height);
+    app.scroll = app.scroll.min(max_scroll);
+
+    let definition = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(with_headword(
+                    definition_title(app),
+                    app.word_index.get(app.selected_index),
+                ))
+                .title_style(accent(app))
+                .title_bottom(status_line(app))
+                .title(scroll_position(app.scroll, max_scroll)),
+        )
+        .scroll((app.scroll, 0))
+        .wrap(Wrap { trim: true });
+    f.render_widget(definition, definition_area);
+    render_scrollbar(f, app, definition_area, max_scroll);
+
+    let status = Paragraph::new(format!(
+        "db: {}",
+        app.databases.get(app.dictionary_index).unwrap()
+    ))
+    .style(accent(app))
+    .block(Block::default());
+    f.render_widget(status, status_area);
+}
+
+#[cfg(feature = "leitner")]
+fn render_leitner_mode(f: &mut Frame, app: &mut App) {
+    let vertical = Layout::horizontal([Constraint::Length(18), Constraint::Min(24)]);
+    let [words_area, definition_area] = vertical.areas(f.area());
+    if app.leitner.word_index.is_empty() {
+        let empty_list = List::new(vec![Span::from("Empty")])
+            .block(Block::default().borders(Borders::ALL).title("Index"));
+        f.render_widget(empty_list, words_area);
+
+        let empty_definition = Paragraph::new("Use ~ (`) key to add a word to Leitner.")
+            .block(Block::default().borders(Borders::ALL).title("Definition"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty_definition, definition_area);
+
+        return;
+    }
+    let height = (words_area.height as usize).saturating_sub(2);
+    let before = max(app.leitner.selected_index as isize - height as isize / 2, 0) as usize;
+    let after = min(
+        app.leitner.selected_index + height,
+        app.leitner.word_index.len(),
+    );
+    let word_index: Vec<String> = app.leitner.word_index[before..after].to_vec();
     let word_index = List::new(word_index)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title(leitner::get_box_symbol(
                     app.leitner.box_level[app.leitner.selected_index],
+                    app.leitner.box_count(),
                 ))
+                .title(Line::from(format!("{} due", app.leitner.due_count())).right_aligned())
                 .title_bottom(leitner::get_relative_date(
                     app.leitner.review_due[app.leitner.selected_index],
-                )),
+                    chrono::Local::now().date_naive(),
+                    &app.config.leitner,
+                ))
+                .title_bottom(
+                    Line::from(leitner::get_attempts_symbol(
+                        app.leitner.attempts[app.leitner.selected_index],
+                    ))
+                    .right_aligned(),
+                ),
         )
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::White));
+        .highlight_style(app.config.theme.highlight);
     let mut state =
         ListState::default().with_selected(Some(min(app.leitner.selected_index, height / 2)));
     f.render_stateful_widget(word_index, words_area, &mut state);
-    let max_scroll = calculate_max_scroll(
-        app.definition.as_str(),
-        definition_area.width,
-        definition_area.height,
-    );
+    let text = Text::from(app.definition.as_str());
+    let definition_area = reader_area(definition_area, app.config.ui.max_width);
+    let max_scroll = calculate_max_scroll(&text, definition_area.width, definition_area.height);
     app.scroll = app.scroll.min(max_scroll);
 
-    let definition = Paragraph::new(app.definition.as_str())
-        .block(Block::default().borders(Borders::ALL).title("Definition"))
+    let definition = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(with_headword(
+                    "Definition".to_string(),
+                    app.leitner.word_index.get(app.leitner.selected_index),
+                ))
+                .title(scroll_position(app.scroll, max_scroll)),
+        )
         .scroll((app.scroll, 0))
         .wrap(Wrap { trim: true });
     f.render_widget(definition, definition_area);
+    render_scrollbar(f, app, definition_area, max_scroll);
+}
+/// Narrows `area` to `max_width` columns of text plus borders, centered.
+fn reader_area(area: Rect, max_width: Option<u16>) -> Rect {
+    match max_width {
+        Some(width) if area.width > width.saturating_add(2) => {
+            let [area] = Layout::horizontal([Constraint::Length(width + 2)])
+                .flex(layout::Flex::Center)
+                .areas(area);
+            area
+        }
+        _ => area,
+    }
+}
+
+/// Draws a scrollbar over the right border of a definition pane.
+fn render_scrollbar(f: &mut Frame, app: &App, area: Rect, max_scroll: u16) {
+    if !app.config.ui.scrollbar || max_scroll == 0 {
+        return;
+    }
+    let mut state = ScrollbarState::new(max_scroll as usize + 1).position(app.scroll as usize);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None);
+    f.render_stateful_widget(
+        scrollbar,
+        area.inner(Margin {
+            vertical: 1,
+            horizontal: 0,
+        }),
+        &mut state,
+    );
+}
+
+fn definition_text(app: &App) -> Text<'static> {
+    if app.show_raw {
+        if let Some(raw) = app.raw_definition() {
+            return Text::from(escape_controls(&raw));
+        }
+    }
+    let text = if app.current_dictionary().ansi {
+        ansi::to_text(&app.definition)
+    } else if let Some(pattern) = &app.definition_pattern {
+        highlight_matches(&app.definition, pattern)
+    } else {
+        Text::from(app.definition.clone())
+    };
+    let mut text = match app.config.ui.fold_lines {
+        Some(lines) if !app.expanded => {
+            fold(text, lines, action_keys(app, Action::ToggleFold).as_deref())
+        }
+        _ => text,
+    };
+    if let Some((_, words)) = &app.suggestions {
+        let mut note = suggestions_line(words, app.suggestion);
+        if let Some(keys) = action_keys(app, Action::Search) {
+            let chosen = &words[app.suggestion];
+            note.push_span(format!(" {} again looks up '{}'", keys, chosen));
+            match action_keys(app, Action::NextLink) {
+                Some(next) if words.len() > 1 => {
+                    note.push_span(format!(", {} picks another.", next));
+                }
+                _ => note.push_span("."),
+            }
+        }
+        text.lines.insert(0, note.italic());
+        text.lines.insert(1, Line::default());
+    }
+    if !app.see_also.is_empty() {
+        text.lines.push(Line::default());
+        text.lines.push(see_also_line(&app.see_also, app.link));
+    }
+    text
+}
+
+/// The note listing the headwords closest to a search that found nothing,
+/// with the `selected` one reversed.
+fn suggestions_line(words: &[String], selected: usize) -> Line<'static> {
+    let mut spans = vec![Span::from("Did you mean ")];
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::from(", "));
+        }
+        let span = Span::from(word.clone());
+        spans.push(match i == selected {
+            true => span.reversed(),
+            false => span,
+        });
+    }
+    spans.push(Span::from("?"));
+    Line::from(spans)
+}
+
+/// The footer listing the related headwords, with the `selected` one
+/// reversed.
+fn see_also_line(words: &[String], selected: Option<usize>) -> Line<'static> {
+    let mut spans = vec![Span::from("See also: ").bold()];
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::from(", "));
+        }
+        let span = Span::from(word.clone()).underlined();
+        spans.push(match selected == Some(i) {
+            true => span.reversed(),
+            false => span,
+        });
+    }
+    Line::from(spans)
+}
+
+/// The keys bound to `action` while browsing, as shown in the help.
+fn action_keys(app: &App, action: Action) -> Option<String> {
+    app.keymap
+        .actions(&[Context::Browse])
+        .into_iter()
+        .find(|(bound, _)| *bound == action)
+        .map(|(_, keys)| keys)
+}
+
+/// Cuts `text` to its first `lines` lines followed by a note on how many
+/// are hidden and the `key` expanding them. A single hidden line is kept.
+fn fold(mut text: Text<'static>, lines: usize, key: Option<&str>) -> Text<'static> {
+    let hidden = text.lines.len().saturating_sub(lines);
+    if hidden <= 1 {
+        return text;
+    }
+    text.lines.truncate(lines);
+    let note = match key {
+        Some(key) => format!("… ({} more lines, {} to expand)", hidden, key),
+        None => format!("… ({} more lines)", hidden),
+    };
+    text.lines.push(Line::from(note).italic());
+    text
+}
+
+/// Splits `text` into lines with the matches of `pattern` reversed.
+fn highlight_matches(text: &str, pattern: &Regex) -> Text<'static> {
+    let lines = text.lines().map(|line| {
+        let mut spans = Vec::new();
+        let mut end = 0;
+        for found in pattern.find_iter(line).filter(|found| !found.is_empty()) {
+            spans.push(Span::raw(line[end..found.start()].to_string()));
+            spans.push(Span::styled(
+                found.as_str().to_string(),
+                Style::new().add_modifier(Modifier::REVERSED),
+            ));
+            end = found.end();
+        }
+        spans.push(Span::raw(line[end..].to_string()));
+        Line::from(spans)
+    });
+    Text::from(lines.collect::<Vec<_>>())
+}
+
+/// Formats an entry as a Markdown blockquote with the word in bold.
+#[cfg_attr(not(feature = "clipboard"), allow(dead_code))]
+fn markdown_quote(word: &str, definition: &str) -> String {
+    let mut quote = format!("> **{}**\n", word);
+    for line in definition.replace("\r\n", "\n").replace('\r', "\n").lines() {
+        quote.push('>');
+        if !line.is_empty() {
+            quote.push(' ');
+            quote.push_str(line);
+        }
+        quote.push('\n');
+    }
+    quote
+}
+
+/// Returns the word drawn at `column`, `row` of `buffer`, inside the borders
+/// of `area`.
+fn word_at(buffer: &Buffer, area: Rect, column: u16, row: u16) -> Option<String> {
+    let inner = area.inner(Margin::new(1, 1));
+    if !inner.contains(Position::new(column, row)) || !buffer.area.contains(inner.as_position()) {
+        return None;
+    }
+    // Wide characters leave an empty symbol in the cell after them.
+    let is_word = |x: u16| {
+        let symbol = buffer[(x, row)].symbol();
+        symbol.is_empty()
+            || symbol
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '\'' || c == '-')
+    };
+    if !is_word(column) {
+        return None;
+    }
+    let mut start = column;
+    while start > inner.x && is_word(start - 1) {
+        start -= 1;
+    }
+    let mut end = column;
+    while end + 1 < inner.right() && is_word(end + 1) {
+        end += 1;
+    }
+    let word: String = (start..=end).map(|x| buffer[(x, row)].symbol()).collect();
+    let word = word.trim_matches(['\'', '-']);
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+/// Makes control characters other than newlines visible, e.g. `\r` and `\x1b`.
+fn escape_controls(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\n' => "\n".to_string(),
+            c if c.is_control() => c.escape_debug().to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn definition_title(app: &App) -> String {
+    let mut title = "Definition".to_string();
+    if app.is_favorite {
+        title.push_str(" \u{2605}");
+    }
+    if app.show_raw {
+        title.push_str(" [raw]");
+    }
+    let headword = app.word_index.get(app.selected_index);
+    if let (Some(input), Some(headword)) = (&app.stemmed_from, headword) {
+        title.push_str(&format!(" [{} \u{2192} {}]", input, headword));
+    }
+    title
+}
+
+/// Style marking the panes of the open dictionary with its configured color.
+fn accent(app: &App) -> Style {
+    match app.current_dictionary().color {
+        Some(color) => Style::default().fg(color),
+        None => Style::default(),
+    }
+}
+
+/// The current status message, if any.
+fn status_line(app: &App) -> Line<'static> {
+    match &app.status {
+        Some((message, _)) => Line::from(message.clone()).italic(),
+        None => Line::default(),
+    }
+}
+
+/// Appends the word the definition belongs to, in bold, to a pane title.
+fn with_headword(title: String, word: Option<&String>) -> Line<'static> {
+    match word {
+        Some(word) => Line::from(vec![
+            Span::raw(format!("{}: ", title)),
+            Span::styled(word.clone(), Style::default().add_modifier(Modifier::BOLD)),
+        ]),
+        None => Line::from(title),
+    }
+}
+
+/// Which of the `matches` listed while filtering is selected, e.g. "3/12";
+/// empty when the whole index is listed.
+fn match_count(cursor: usize, matches: Option<usize>) -> Line<'static> {
+    match matches {
+        Some(0) => Line::from("0 matches").right_aligned(),
+        Some(count) => Line::from(format!("{}/{}", cursor + 1, count)).right_aligned(),
+        None => Line::default(),
+    }
+}
+
+/// How far the definition is scrolled, as a right-aligned percentage; empty
+/// when it fits in the pane.
+fn scroll_position(scroll: u16, max_scroll: u16) -> Line<'static> {
+    if max_scroll == 0 {
+        return Line::default();
+    }
+    let percent = u32::from(scroll.min(max_scroll)) * 100 / u32::from(max_scroll);
+    Line::from(format!("{}%", percent)).right_aligned()
 }
-fn calculate_max_scroll(content: &str, area_width: u16, area_height: u16) -> u16 {
-    let wrapped_lines = content
-        .lines()
-        .map(|line| (line.len() as u16).div_ceil(area_width - 2))
-        .sum::<u16>();
-    (wrapped_lines).saturating_sub(area_height / 2)
+
+/// Returns how far `content` can be scrolled in a bordered pane of the given
+/// size before its last line reaches the bottom. The wrapped line count comes
+/// from ratatui's own word wrapper so it matches what is drawn.
+fn calculate_max_scroll(content: &Text, area_width: u16, area_height: u16) -> u16 {
+    let wrapped_lines = Paragraph::new(content.clone())
+        .wrap(Wrap { trim: true })
+        .line_count(area_width.saturating_sub(2));
+    let wrapped_lines = u16::try_from(wrapped_lines).unwrap_or(u16::MAX);
+    wrapped_lines.saturating_sub(area_height.saturating_sub(2))
 }
 
 fn delete_last_word(buffer: &mut String) {
@@ -529,3 +3300,203 @@ fn delete_last_word(buffer: &mut String) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app over dictionaries created in a fresh data folder for `test`,
+    /// each given as its name and entries, with the first one open.
+    fn app_with(test: &str, dictionaries: &[(&str, &[(&str, &str)])]) -> App {
+        let datapath = std::env::temp_dir().join(format!("dicrs-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&datapath);
+        let dicpath = datapath.join("dictionaries");
+        fs::create_dir_all(&dicpath).unwrap();
+        for (name, entries) in dictionaries {
+            let conn = Connection::open(dicpath.join(format!("{}.db", name))).unwrap();
+            conn.execute("CREATE TABLE dictionary (word TEXT, definition TEXT)", [])
+                .unwrap();
+            for (word, definition) in *entries {
+                conn.execute("INSERT INTO dictionary VALUES (?1, ?2)", [word, definition])
+                    .unwrap();
+            }
+        }
+        let mut app = App::default(Config::default(), datapath, dicpath, Mode::Default).unwrap();
+        app.create(app.database_file(0));
+        app
+    }
+
+    #[test]
+    fn the_selection_survives_reloading_a_large_index() {
+        let mut app = app_with("large", &[("large", &[])]);
+        Connection::open(&app.database_path)
+            .unwrap()
+            .execute_batch(&format!(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {})
+                 INSERT INTO dictionary SELECT printf('word%06d', i), 'A word.' FROM n;",
+                INDEX_CHUNK + 10
+            ))
+            .unwrap();
+        app.create(app.database_path.clone());
+        assert!(!app.index_loaded());
+        app.perform(Action::Bottom);
+        assert_eq!(app.selected_index, INDEX_CHUNK + 9);
+        app.refresh();
+        assert_eq!(
+            app.word_index[app.selected_index],
+            format!("word{:06}", INDEX_CHUNK + 10)
+        );
+    }
+
+    #[test]
+    fn enter_reverse_searches_the_edited_input() {
+        let mut app = app_with(
+            "reverse",
+            &[(
+                "one",
+                &[("cat", "A small animal."), ("dog", "A loyal animal.")],
+            )],
+        );
+        app.input = "small".to_string();
+        app.perform(Action::ReverseSearch);
+        assert_eq!(app.filter, Some(vec![0]));
+        app.input = "loyal".to_string();
+        app.perform(Action::Search);
+        assert_eq!(app.filter, Some(vec![1]));
+        assert!(app.definition_pattern.unwrap().is_match("Loyal"));
+    }
+
+    #[test]
+    fn enter_filters_definitions_by_the_edited_pattern() {
+        let mut app = app_with(
+            "definitions",
+            &[(
+                "one",
+                &[("cat", "A small animal."), ("dog", "A loyal animal.")],
+            )],
+        );
+        app.input = "sm.ll".to_string();
+        app.perform(Action::SearchDefinitions);
+        assert_eq!(app.filter, Some(vec![0]));
+        app.input = "lo?yal".to_string();
+        app.perform(Action::Search);
+        assert_eq!(app.filter, Some(vec![1]));
+        assert_eq!(app.definition_pattern.unwrap().as_str(), "lo?yal");
+    }
+
+    #[test]
+    fn keys_of_an_unfinished_sequence_are_typed() {
+        let mut app = app_with("sequence", &[("one", &[("cat", "An animal.")])]);
+        app.config.ui.live_search_delay = Duration::ZERO;
+        let g = Key::new(KeyCode::Char('g'), KeyModifiers::NONE);
+        app.keymap.bind(Context::Browse, vec![g, g], Action::Top);
+        let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_key(press('g'));
+        assert_eq!(app.input, "");
+        app.handle_key(press('o'));
+        assert_eq!(app.input, "go");
+        app.handle_key(press('g'));
+        app.pending_since = Instant::now() - SEQUENCE_TIMEOUT;
+        app.tick();
+        assert_eq!(app.input, "gog");
+    }
+
+    #[test]
+    fn enter_builds_the_full_text_index_after_switching_dictionaries() {
+        let mut app = app_with(
+            "full-text",
+            &[
+                ("one", &[("cat", "A small animal.")]),
+                ("two", &[("bird", "A plant."), ("dog", "A loyal animal.")]),
+            ],
+        );
+        app.input = "animal".to_string();
+        app.perform(Action::ToggleFullText);
+        assert_eq!(app.filter, Some(vec![0]));
+        app.perform(Action::NextDictionary);
+        assert_eq!(app.filter, Some(vec![]));
+        app.perform(Action::Search);
+        assert_eq!(app.filter, Some(vec![1]));
+        assert_eq!(app.word_index[app.selected_index], "dog");
+    }
+
+    #[test]
+    fn max_scroll_follows_word_wrapping() {
+        // "aaa bbb" fits a 7 column line, so this wraps to two lines.
+        let text = Text::from("aaa bbb ccc");
+        assert_eq!(calculate_max_scroll(&text, 9, 3), 1);
+        assert_eq!(calculate_max_scroll(&text, 9, 4), 0);
+        // Four columns fit one word per line, which the character count
+        // alone (11 / 4 = 3 lines) underestimates.
+        assert_eq!(calculate_max_scroll(&text, 6, 3), 2);
+    }
+
+    #[test]
+    fn max_scroll_breaks_long_words() {
+        let text = Text::from("abcdefghij");
+        assert_eq!(calculate_max_scroll(&text, 7, 3), 1);
+    }
+
+    #[test]
+    fn max_scroll_counts_every_line() {
+        let text = Text::from("one\ntwo\n\nfour");
+        assert_eq!(calculate_max_scroll(&text, 20, 4), 2);
+    }
+
+    #[test]
+    fn long_definitions_are_folded() {
+        let text = Text::from("1\n2\n3\n4");
+        assert_eq!(fold(text.clone(), 3, None), text);
+        let folded = fold(text, 2, Some("Alt + Z"));
+        assert_eq!(folded.lines.len(), 3);
+        assert_eq!(
+            folded.lines[2].to_string(),
+            "… (2 more lines, Alt + Z to expand)"
+        );
+    }
+
+    #[test]
+    fn scroll_position_is_a_clamped_percentage() {
+        assert_eq!(scroll_position(0, 0), Line::default());
+        assert_eq!(scroll_position(0, 5), Line::from("0%").right_aligned());
+        assert_eq!(scroll_position(2, 5), Line::from("40%").right_aligned());
+        assert_eq!(scroll_position(9, 5), Line::from("100%").right_aligned());
+    }
+
+    #[test]
+    fn match_count_shows_the_selected_match() {
+        assert_eq!(match_count(2, None), Line::default());
+        assert_eq!(match_count(2, Some(12)), Line::from("3/12").right_aligned());
+        assert_eq!(
+            match_count(0, Some(0)),
+            Line::from("0 matches").right_aligned()
+        );
+    }
+
+    #[test]
+    fn max_scroll_handles_tiny_areas() {
+        let text = Text::from("word");
+        assert_eq!(calculate_max_scroll(&text, 1, 1), 0);
+    }
+
+    #[test]
+    fn markdown_quote_prefixes_every_line() {
+        assert_eq!(
+            markdown_quote("word", "first\r\rsecond"),
+            "> **word**\n> first\n>\n> second\n"
+        );
+    }
+
+    #[test]
+    fn word_at_finds_the_clicked_word() {
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buffer = Buffer::empty(area);
+        Paragraph::new("a well-known word.")
+            .block(Block::default().borders(Borders::ALL))
+            .render(area, &mut buffer);
+        assert_eq!(word_at(&buffer, area, 5, 1).as_deref(), Some("well-known"));
+        assert_eq!(word_at(&buffer, area, 16, 1).as_deref(), Some("word"));
+        assert_eq!(word_at(&buffer, area, 2, 1), None);
+        assert_eq!(word_at(&buffer, area, 5, 0), None);
+    }
+}
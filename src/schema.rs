@@ -0,0 +1,249 @@
+use rusqlite::Connection;
+
+use crate::config::DictionaryConfig;
+
+/// Column names tried, in order, when a dictionary does not configure its
+/// word column.
+static WORD_COLUMNS: [&str; 5] = ["word", "term", "headword", "entry", "lemma"];
+/// Column names tried, in order, for the definition column.
+static DEFINITION_COLUMNS: [&str; 5] = [
+    "definition",
+    "meaning",
+    "gloss",
+    "description",
+    "translation",
+];
+
+/// Column listing related headwords, comma-separated, when a dictionary has
+/// one.
+static SEE_ALSO_COLUMN: &str = "see_also";
+
+/// Definitions sampled to detect the line separator.
+const NEWLINE_SAMPLE: usize = 100;
+
+/// How line breaks are stored in the definitions, and turned into `\n`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Newlines {
+    /// Definitions are shown as stored.
+    None,
+    /// `\r` starts a new line.
+    #[default]
+    Cr,
+    /// `\r\n` starts a new line.
+    CrLf,
+    /// HTML `<br>` tags start a new line.
+    Br,
+}
+
+impl Newlines {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Newlines::None),
+            "cr" => Some(Newlines::Cr),
+            "crlf" => Some(Newlines::CrLf),
+            "br" => Some(Newlines::Br),
+            _ => None,
+        }
+    }
+
+    /// Picks the separator found in a sample of the definitions, falling
+    /// back to `Cr`.
+    fn detect(conn: &Connection, definition: &str) -> Self {
+        let sql = format!(
+            "SELECT {} FROM dictionary LIMIT {}",
+            quote_identifier(definition),
+            NEWLINE_SAMPLE
+        );
+        let Ok(mut stmt) = conn.prepare(&sql) else {
+            return Self::default();
+        };
+        let samples: Vec<String> = stmt
+            .query_map([], |row| row.get::<_, Option<String>>(0))
+            .map(|rows| rows.flatten().flatten().collect())
+            .unwrap_or_default();
+        if samples.iter().any(|text| text.contains("\r\n")) {
+            Newlines::CrLf
+        } else if samples.iter().any(|text| text.contains('\r')) {
+            Newlines::Cr
+        } else if samples.iter().any(|text| break_at(text).is_some()) {
+            Newlines::Br
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Turns the line separators of `text` into `\n`.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Newlines::None => text.to_string(),
+            Newlines::Cr => text.replace('\r', "\n"),
+            Newlines::CrLf => text.replace("\r\n", "\n"),
+            Newlines::Br => {
+                let mut lines = String::with_capacity(text.len());
+                let mut rest = text;
+                while let Some((start, len)) = break_at(rest) {
+                    lines.push_str(&rest[..start]);
+                    lines.push('\n');
+                    rest = &rest[start + len..];
+                }
+                lines.push_str(rest);
+                lines
+            }
+        }
+    }
+
+    /// Turns the `\n` line breaks of `text` back into this separator, as
+    /// stored in the dictionary.
+    pub fn restore(self, text: &str) -> String {
+        match self {
+            Newlines::None => text.to_string(),
+            Newlines::Cr => text.replace('\n', "\r"),
+            Newlines::CrLf => text.replace('\n', "\r\n"),
+            Newlines::Br => text.replace('\n', "<br>"),
+        }
+    }
+}
+
+/// Position and length of the first `<br>`, `<br/>` or `<br />` tag in
+/// `text`, ignoring case.
+fn break_at(text: &str) -> Option<(usize, usize)> {
+    text.match_indices('<').find_map(|(start, _)| {
+        ["<br>", "<br/>", "<br />"].iter().find_map(|tag| {
+            text.get(start..start + tag.len())
+                .filter(|found| found.eq_ignore_ascii_case(tag))
+                .map(|_| (start, tag.len()))
+        })
+    })
+}
+
+/// Names of the columns holding the words and definitions in a dictionary's
+/// `dictionary` table, and how line breaks are stored in the definitions.
+pub struct Schema {
+    pub word: String,
+    pub definition: String,
+    pub see_also: Option<String>,
+    pub newlines: Newlines,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            word: WORD_COLUMNS[0].to_string(),
+            definition: DEFINITION_COLUMNS[0].to_string(),
+            see_also: None,
+            newlines: Newlines::default(),
+        }
+    }
+}
+
+impl Schema {
+    /// Uses the columns configured for the dictionary and looks for common
+    /// alternatives to `word` and `definition` when none are configured.
+    /// The line separator is detected from a sample of the definitions
+    /// unless configured too.
+    pub fn detect(conn: &Connection, config: &DictionaryConfig) -> Self {
+        let columns = table_columns(conn);
+        let find = |configured: &Option<String>, candidates: &[&str]| match configured {
+            Some(name) => name.clone(),
+            None => candidates
+                .iter()
+                .find_map(|candidate| {
+                    columns
+                        .iter()
+                        .find(|column| column.eq_ignore_ascii_case(candidate))
+                })
+                .cloned()
+                .unwrap_or_else(|| candidates[0].to_string()),
+        };
+        let definition = find(&config.definition_column, &DEFINITION_COLUMNS);
+        Self {
+            word: find(&config.word_column, &WORD_COLUMNS),
+            newlines: config
+                .newlines
+                .unwrap_or_else(|| Newlines::detect(conn, &definition)),
+            definition,
+            see_also: columns
+                .iter()
+                .find(|column| column.eq_ignore_ascii_case(SEE_ALSO_COLUMN))
+                .cloned(),
+        }
+    }
+
+    /// Fills the `{word}`, `{definition}` and `{see_also}` placeholders of
+    /// `template` with the quoted column names. `{see_also}` becomes `NULL`
+    /// when there is no such column.
+    pub fn sql(&self, template: &str) -> String {
+        let see_also = match &self.see_also {
+            Some(column) => quote_identifier(column),
+            None => "NULL".to_string(),
+        };
+        template
+            .replace("{word}", &quote_identifier(&self.word))
+            .replace("{definition}", &quote_identifier(&self.definition))
+            .replace("{see_also}", &see_also)
+    }
+}
+
+/// Names of the columns of the `dictionary` table, empty when it is missing.
+fn table_columns(conn: &Connection) -> Vec<String> {
+    let Ok(mut stmt) = conn.prepare("SELECT name FROM pragma_table_info('dictionary')") else {
+        return Vec::new();
+    };
+    stmt.query_map([], |row| row.get(0))
+        .map(|rows| rows.flatten().collect())
+        .unwrap_or_default()
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_alternative_column_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE dictionary (Term TEXT, meaning TEXT)", [])
+            .unwrap();
+        let schema = Schema::detect(&conn, &DictionaryConfig::default());
+        assert_eq!(schema.word, "Term");
+        assert_eq!(schema.definition, "meaning");
+        assert_eq!(schema.see_also, None);
+        assert_eq!(
+            schema.sql("SELECT {word}, {definition} FROM dictionary"),
+            "SELECT \"Term\", \"meaning\" FROM dictionary"
+        );
+        assert_eq!(schema.sql("{see_also}"), "NULL");
+    }
+
+    #[test]
+    fn line_separators_are_detected_and_replaced() {
+        let detect = |definitions: &str| {
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(&format!(
+                "CREATE TABLE dictionary (word TEXT, definition TEXT);
+                 INSERT INTO dictionary VALUES {};",
+                definitions
+            ))
+            .unwrap();
+            Schema::detect(&conn, &DictionaryConfig::default()).newlines
+        };
+        assert_eq!(
+            detect("('a', 'one'), ('b', 'one' || char(13, 10) || 'two')"),
+            Newlines::CrLf
+        );
+        assert_eq!(detect("('a', 'one' || char(13) || 'two')"), Newlines::Cr);
+        assert_eq!(detect("('a', 'one<BR/>two'), ('b', NULL)"), Newlines::Br);
+        assert_eq!(detect("('a', 'one')"), Newlines::Cr);
+        assert_eq!(Newlines::Br.apply("a<br>b<Br />c<b>"), "a\nb\nc<b>");
+        assert_eq!(Newlines::CrLf.apply("a\r\nb"), "a\nb");
+        assert_eq!(Newlines::None.apply("a\rb"), "a\rb");
+        for newlines in [Newlines::Cr, Newlines::CrLf, Newlines::Br] {
+            assert_eq!(newlines.apply(&newlines.restore("a\nb")), "a\nb");
+        }
+        assert_eq!(Newlines::CrLf.restore("a\nb"), "a\r\nb");
+        assert_eq!(Newlines::None.restore("a\nb"), "a\nb");
+    }
+}
@@ -0,0 +1,638 @@
+use regex::{Regex, RegexBuilder};
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// How the input is matched against the headwords.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SearchMode {
+    #[default]
+    Prefix,
+    Contains,
+    /// Words with the words of the input in the same order, possibly with
+    /// others between them, each typed word starting one of the headword's.
+    Phrase,
+    Exact,
+    /// Words within a few typos of the input, the closest first.
+    Fuzzy,
+    /// Words matching the input as a regular expression, ignoring case.
+    Regex,
+    /// Words made of the same letters as the input, in any order.
+    Anagram,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Prefix => SearchMode::Contains,
+            SearchMode::Contains => SearchMode::Phrase,
+            SearchMode::Phrase => SearchMode::Exact,
+            SearchMode::Exact => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Anagram,
+            SearchMode::Anagram => SearchMode::Prefix,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            SearchMode::Prefix,
+            SearchMode::Contains,
+            SearchMode::Phrase,
+            SearchMode::Exact,
+            SearchMode::Fuzzy,
+            SearchMode::Regex,
+            SearchMode::Anagram,
+        ]
+        .into_iter()
+        .find(|mode| mode.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchMode::Prefix => "prefix",
+            SearchMode::Contains => "contains",
+            SearchMode::Phrase => "phrase",
+            SearchMode::Exact => "exact",
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Regex => "regex",
+            SearchMode::Anagram => "anagram",
+        }
+    }
+
+    /// `query` as matched against folded words: folded, except that regular
+    /// expressions keep their case, as lowercasing would change escapes such
+    /// as `\W`; they are compiled to ignore case instead.
+    pub fn fold(self, query: &str) -> String {
+        match self {
+            SearchMode::Regex => strip_marks(query),
+            _ => fold(query),
+        }
+    }
+
+    /// Whether `query` has wildcards, which the regex mode reads as regular
+    /// expression syntax instead and the anagram mode ignores.
+    pub fn is_glob(self, query: &str) -> bool {
+        !matches!(self, SearchMode::Regex | SearchMode::Anagram) && has_wildcards(query)
+    }
+
+    /// Whether all the matches of `query` are listed in the index rather
+    /// than only the first being selected.
+    pub fn lists_matches(self, query: &str) -> bool {
+        matches!(
+            self,
+            SearchMode::Phrase | SearchMode::Fuzzy | SearchMode::Regex | SearchMode::Anagram
+        ) || has_wildcards(query)
+    }
+
+    /// Whether matches are found over the in-memory index rather than with
+    /// `like_pattern`, which cannot express them.
+    pub fn needs_index(self, query: &str) -> bool {
+        self.is_ranked(query)
+            || matches!(
+                self,
+                SearchMode::Phrase | SearchMode::Regex | SearchMode::Anagram
+            )
+    }
+
+    /// Whether the matches of `query` are ranked, the closest first, rather
+    /// than listed in index order: fuzzy searches without wildcards.
+    pub fn is_ranked(self, query: &str) -> bool {
+        self == SearchMode::Fuzzy && !has_wildcards(query)
+    }
+
+    /// Builds a pattern for `word LIKE ?1 ESCAPE '\'` matching `query`. LIKE
+    /// cannot tolerate typos, so a fuzzy pattern only matches `query` itself.
+    /// Wildcards in `query` become `%` and `_`, matching the whole word.
+    /// Phrase, regular expression and anagram patterns also only match
+    /// `query` itself.
+    pub fn like_pattern(self, query: &str) -> String {
+        let wildcards = self.is_glob(query);
+        let mut escaped = String::with_capacity(query.len());
+        for c in query.chars() {
+            match c {
+                '*' if wildcards => escaped.push('%'),
+                '?' if wildcards => escaped.push('_'),
+                '%' | '_' | '\\' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                _ => escaped.push(c),
+            }
+        }
+        if wildcards {
+            return escaped;
+        }
+        match self {
+            SearchMode::Prefix => format!("{}%", escaped),
+            SearchMode::Contains => format!("%{}%", escaped),
+            SearchMode::Phrase
+            | SearchMode::Exact
+            | SearchMode::Fuzzy
+            | SearchMode::Regex
+            | SearchMode::Anagram => escaped,
+        }
+    }
+
+    /// Matches `query` against `word`, both already folded. With
+    /// `whole_word`, a contains search only matches `query` as a whole word.
+    /// A `query` with wildcards matches as described at `glob_matches` in
+    /// every mode but the regex one, where an invalid `query` matches
+    /// nothing.
+    pub fn matches(self, word: &str, query: &str, whole_word: bool) -> bool {
+        if self.is_glob(query) {
+            return glob_matches(word, query);
+        }
+        match self {
+            SearchMode::Prefix => word.starts_with(query),
+            SearchMode::Contains if whole_word => contains_word(word, query),
+            SearchMode::Contains => word.contains(query),
+            SearchMode::Phrase => phrase_matches(word, query),
+            SearchMode::Exact => word == query,
+            SearchMode::Fuzzy => {
+                let word: Vec<char> = word.chars().collect();
+                let query: Vec<char> = query.chars().collect();
+                edit_distance(&query, &word, typo_limit_of(query.len())).is_some()
+            }
+            SearchMode::Regex => word_regex(query).is_ok_and(|regex| regex.is_match(word)),
+            SearchMode::Anagram => {
+                let key = anagram_key(query);
+                !key.is_empty() && anagram_key(word) == key
+            }
+        }
+    }
+}
+
+/// Position of the first of `words` matching `query`, all folded, or of
+/// the closest one in a fuzzy search.
+pub fn first_match(
+    words: &[String],
+    query: &str,
+    mode: SearchMode,
+    whole_word: bool,
+) -> Option<usize> {
+    if mode.is_ranked(query) {
+        return ranked_matches(words, query).first().copied();
+    }
+    if mode == SearchMode::Regex {
+        let regex = word_regex(query).ok()?;
+        return words.iter().position(|word| regex.is_match(word));
+    }
+    words
+        .iter()
+        .position(|word| mode.matches(word, query, whole_word))
+}
+
+/// Position of the best of `words` matching `query`, all folded, in a prefix
+/// or contains search: an exact match first, then one starting with `query`,
+/// then the first match. Other searches take `first_match`.
+pub fn best_match(
+    words: &[String],
+    query: &str,
+    mode: SearchMode,
+    whole_word: bool,
+) -> Option<usize> {
+    if matches!(mode, SearchMode::Prefix | SearchMode::Contains) && !mode.is_glob(query) {
+        if let Some(exact) = words.iter().position(|word| word == query) {
+            return Some(exact);
+        }
+        let prefixed = words
+            .iter()
+            .position(|word| word.starts_with(query) && mode.matches(word, query, whole_word));
+        if prefixed.is_some() {
+            return prefixed;
+        }
+    }
+    first_match(words, query, mode, whole_word)
+}
+
+/// The Snowball stemmer for a language named in lowercase, e.g. "english".
+pub fn stemmer(language: &str) -> Option<Algorithm> {
+    use Algorithm::*;
+    [
+        Arabic, Danish, Dutch, English, Finnish, French, German, Greek, Hungarian, Italian,
+        Norwegian, Portuguese, Romanian, Russian, Spanish, Swedish, Tamil, Turkish,
+    ]
+    .into_iter()
+    .find(|algorithm| format!("{:?}", algorithm).to_lowercase() == language)
+}
+
+/// Position of a headword of `words`, all folded, sharing its stem with
+/// `query`: the stem itself if it is a headword, or else the first with the
+/// same stem, so that "running" finds "run".
+pub fn stem_match(words: &[String], query: &str, algorithm: Algorithm) -> Option<usize> {
+    let stemmer = Stemmer::create(algorithm);
+    let stem = stemmer.stem(query);
+    words
+        .iter()
+        .position(|word| *word == stem)
+        .or_else(|| words.iter().position(|word| stemmer.stem(word) == stem))
+}
+
+/// Positions of all of `words` matching `query`, all folded. Fuzzy
+/// matches are ranked, the closest first.
+pub fn all_matches(
+    words: &[String],
+    query: &str,
+    mode: SearchMode,
+    whole_word: bool,
+) -> Vec<usize> {
+    if mode.is_ranked(query) {
+        return ranked_matches(words, query);
+    }
+    if mode == SearchMode::Regex {
+        let Ok(regex) = word_regex(query) else {
+            return Vec::new();
+        };
+        return words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| regex.is_match(word))
+            .map(|(i, _)| i)
+            .collect();
+    }
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| mode.matches(word, query, whole_word))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Folds `text` for matching that ignores case and diacritics: lowercased,
+/// with accents and other combining marks removed, so that "Café" and "cafe"
+/// fold the same. Runs of whitespace become a single space, so that phrases
+/// match however they are spaced.
+pub fn fold(text: &str) -> String {
+    collapse_whitespace(strip_marks(&text.to_lowercase()))
+}
+
+fn collapse_whitespace(text: String) -> String {
+    if !text.contains(|c: char| c.is_whitespace() && c != ' ') && !text.contains("  ") {
+        return text;
+    }
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c.is_whitespace() {
+            true if collapsed.ends_with(' ') => {}
+            true => collapsed.push(' '),
+            false => collapsed.push(c),
+        }
+    }
+    collapsed
+}
+
+fn strip_marks(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    text.nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .nfc()
+        .collect()
+}
+
+/// The letters and digits of `word`, folded, in sorted order: words with the
+/// same key are anagrams of each other, e.g. "listen" and "silent", or
+/// "dormitory" and "dirty room".
+pub fn anagram_key(word: &str) -> String {
+    let mut letters: Vec<char> = fold(word).chars().filter(|c| c.is_alphanumeric()).collect();
+    letters.sort_unstable();
+    letters.into_iter().collect()
+}
+
+/// Compiles a regex search `query`, ignoring case.
+pub fn word_regex(query: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(query).case_insensitive(true).build()
+}
+
+/// Whether the words of `query` start words of `text`, in the same order,
+/// e.g. "kick buck" in "kick the bucket".
+pub fn phrase_matches(text: &str, query: &str) -> bool {
+    let mut words = text.split(|c: char| !c.is_alphanumeric());
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|typed| !typed.is_empty())
+        .all(|typed| words.any(|word| word.starts_with(typed)))
+}
+
+/// Whether `query` has the wildcards `*` or `?`.
+pub fn has_wildcards(query: &str) -> bool {
+    query.contains(['*', '?'])
+}
+
+/// Whether the whole of `word` matches `pattern`, in which `*` stands for
+/// any run of characters and `?` for any one character.
+pub fn glob_matches(word: &str, pattern: &str) -> bool {
+    let word: Vec<char> = word.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut w, mut p) = (0, 0);
+    // Position of the last `*` and of the word when it was reached, to
+    // backtrack to with the star taking one more character.
+    let mut star: Option<(usize, usize)> = None;
+    while w < word.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, w));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == word[w] => {
+                p += 1;
+                w += 1;
+            }
+            _ => match star {
+                Some((star_p, star_w)) => {
+                    star = Some((star_p, star_w + 1));
+                    p = star_p + 1;
+                    w = star_w + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `query` occurs in `text` bounded by non-alphanumeric characters
+/// or the ends of `text`.
+pub fn contains_word(text: &str, query: &str) -> bool {
+    if query.is_empty() {
+        return true;
+    }
+    text.match_indices(query).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + query.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Whether the characters of `query` appear in `text` in the same order,
+/// ignoring case, like the filtering of the command palette.
+pub fn fuzzy_matches(text: &str, query: &str) -> bool {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| chars.any(|c| c == q))
+}
+
+/// Most typos allowed when suggesting words for `query`: none for one or
+/// two characters, and one more for every four characters after that.
+pub fn typo_limit(query: &str) -> usize {
+    typo_limit_of(query.chars().count())
+}
+
+fn typo_limit_of(len: usize) -> usize {
+    (len + 1) / 4
+}
+
+/// Number of inserted, deleted or replaced characters turning `a` into `b`,
+/// or `None` when it is more than `limit`.
+pub fn edit_distance(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != cb);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|&min| min > limit) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= limit)
+}
+
+/// Positions of `words` within the typo limit of `query`, all folded,
+/// the closest first and otherwise in index order.
+pub fn ranked_matches(words: &[String], query: &str) -> Vec<usize> {
+    let limit = typo_limit(query);
+    let query: Vec<char> = query.chars().collect();
+    let mut matches: Vec<(usize, usize)> = words
+        .iter()
+        .enumerate()
+        .filter_map(|(i, word)| {
+            let word: Vec<char> = word.chars().collect();
+            edit_distance(&query, &word, limit).map(|distance| (distance, i))
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Positions of up to `count` distinct words of `words` within the typo
+/// limit of `query`, all folded, the closest first. Nothing is
+/// suggested for queries too short to allow a typo.
+pub fn closest_matches(words: &[String], query: &str, count: usize) -> Vec<usize> {
+    if typo_limit(query) == 0 {
+        return Vec::new();
+    }
+    let mut closest: Vec<usize> = Vec::new();
+    for i in ranked_matches(words, query) {
+        if closest.len() == count {
+            break;
+        }
+        if !closest.iter().any(|&j| words[j] == words[i]) {
+            closest.push(i);
+        }
+    }
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_word_matches_respect_boundaries() {
+        assert!(contains_word("kick the bucket", "the"));
+        assert!(contains_word("set-aside", "set"));
+        assert!(!contains_word("category", "cat"));
+        assert!(!contains_word("bobcat", "cat"));
+        assert!(SearchMode::Contains.matches("category", "cat", false));
+        assert!(!SearchMode::Contains.matches("category", "cat", true));
+    }
+
+    #[test]
+    fn fuzzy_matches_follow_the_order_of_the_query() {
+        assert!(fuzzy_matches("Toggle the filter", "tgfil"));
+        assert!(fuzzy_matches("Toggle the filter", ""));
+        assert!(!fuzzy_matches("Toggle the filter", "filtg"));
+    }
+
+    #[test]
+    fn close_words_are_suggested_within_the_typo_limit() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(
+            edit_distance(&chars("kitten"), &chars("sitting"), 3),
+            Some(3)
+        );
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting"), 2), None);
+        assert_eq!(typo_limit("ca"), 0);
+        assert_eq!(typo_limit("definately"), 2);
+        let words: Vec<String> = ["definite", "definitely", "definitly", "definitely", "cat"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(closest_matches(&words, "definately", 5), [1, 2]);
+        assert!(closest_matches(&words, "ct", 5).is_empty());
+    }
+
+    #[test]
+    fn fuzzy_searches_rank_words_within_the_typo_limit() {
+        let words: Vec<String> = ["deceive", "receive", "recieve", "relieve"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert!(SearchMode::Fuzzy.matches("receive", "recieve", false));
+        assert!(!SearchMode::Fuzzy.matches("cat", "dog", false));
+        assert_eq!(
+            all_matches(&words, "recieve", SearchMode::Fuzzy, false),
+            [2, 3, 1]
+        );
+        assert_eq!(
+            first_match(&words, "receive", SearchMode::Fuzzy, false),
+            Some(1)
+        );
+        assert_eq!(SearchMode::from_name("fuzzy"), Some(SearchMode::Fuzzy));
+    }
+
+    #[test]
+    fn wildcards_match_whole_words_in_every_mode() {
+        assert!(glob_matches("receive", "rec*ve"));
+        assert!(glob_matches("receive", "*"));
+        assert!(glob_matches("receive", "r?c?iv?"));
+        assert!(glob_matches("aab", "*ab"));
+        assert!(!glob_matches("receiver", "rec*ve"));
+        assert!(!glob_matches("", "?"));
+        assert!(SearchMode::Exact.matches("über", "?ber", false));
+        assert!(!SearchMode::Contains.matches("deceive", "cei?", false));
+        assert!(!SearchMode::Fuzzy.is_ranked("rec*"));
+        assert_eq!(SearchMode::Prefix.like_pattern("a*_?"), "a%\\__");
+    }
+
+    #[test]
+    fn regex_searches_ignore_case_and_wildcards() {
+        let words: Vec<String> = ["apple", "apply", "cat", "über"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(
+            all_matches(&words, "^APP.*[ey]$", SearchMode::Regex, false),
+            [0, 1]
+        );
+        assert_eq!(all_matches(&words, "^\\W", SearchMode::Regex, false), []);
+        assert_eq!(first_match(&words, "t$", SearchMode::Regex, false), Some(2));
+        assert_eq!(first_match(&words, "(", SearchMode::Regex, false), None);
+        assert!(SearchMode::Regex.matches("über", "^Ü", false));
+        assert!(!SearchMode::Regex.matches("cat", "^c?t$", false));
+        assert_eq!(SearchMode::Regex.fold("\\W"), "\\W");
+    }
+
+    #[test]
+    fn folding_ignores_case_and_diacritics() {
+        assert_eq!(fold("Café"), "cafe");
+        assert_eq!(fold("ÜBER"), "uber");
+        assert_eq!(fold("Ångström"), "angstrom");
+        assert_eq!(fold("straße"), "straße");
+        assert_eq!(SearchMode::Regex.fold("^Ü\\W"), "^U\\W");
+        let words: Vec<String> = ["über", "cafe"].iter().map(|w| fold(w)).collect();
+        let query = SearchMode::Prefix.fold("Ube");
+        assert_eq!(
+            first_match(&words, &query, SearchMode::Prefix, false),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn exact_matches_come_before_prefix_and_contains_matches() {
+        let words: Vec<String> = ["offset", "set-aside", "set", "settle"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        let best = |query, mode| best_match(&words, query, mode, false);
+        assert_eq!(best("set", SearchMode::Prefix), Some(2));
+        assert_eq!(best("set", SearchMode::Contains), Some(2));
+        assert_eq!(best("sett", SearchMode::Contains), Some(3));
+        assert_eq!(best("fs", SearchMode::Contains), Some(0));
+        assert_eq!(best("se*", SearchMode::Prefix), Some(1));
+        assert_eq!(best("x", SearchMode::Prefix), None);
+    }
+
+    #[test]
+    fn inflected_words_find_their_stem() {
+        let words: Vec<String> = ["runner", "run", "happy"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        let english = stemmer("english").unwrap();
+        assert_eq!(stem_match(&words, "running", english), Some(1));
+        assert_eq!(stem_match(&words, "happiness", english), Some(2));
+        assert_eq!(stem_match(&words, "walked", english), None);
+        assert_eq!(stemmer("french"), Some(Algorithm::French));
+        assert_eq!(stemmer("klingon"), None);
+    }
+
+    #[test]
+    fn phrases_match_words_in_order() {
+        assert!(phrase_matches("kick the bucket", "kick bucket"));
+        assert!(phrase_matches("kick the bucket", "kick buck"));
+        assert!(phrase_matches("kick the bucket", "the"));
+        assert!(!phrase_matches("kick the bucket", "bucket kick"));
+        assert!(!phrase_matches("kick the bucket", "ick"));
+        assert!(!phrase_matches("set-aside", "set set"));
+        assert_eq!(fold("Kick  the\tBucket"), "kick the bucket");
+        let words: Vec<String> = ["bucket list", "kick the bucket", "kick off"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(
+            all_matches(&words, "kick bucket", SearchMode::Phrase, false),
+            [1]
+        );
+    }
+
+    #[test]
+    fn anagrams_have_the_same_letters() {
+        assert_eq!(anagram_key("Listen"), "eilnst");
+        assert_eq!(anagram_key("dirty room"), anagram_key("dormitory"));
+        assert_eq!(anagram_key("Éclair"), anagram_key("lacier"));
+        let words: Vec<String> = ["enlist", "inlets", "list", "silent", "tinsel", "listens"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(
+            all_matches(&words, "listen", SearchMode::Anagram, false),
+            [0, 1, 3, 4]
+        );
+        assert_eq!(
+            all_matches(&words, "Sil-ent!", SearchMode::Anagram, false),
+            [0, 1, 3, 4]
+        );
+        assert!(all_matches(&words, "--", SearchMode::Anagram, false).is_empty());
+    }
+
+    #[test]
+    fn matches_are_found_in_index_order() {
+        let words: Vec<String> = ["apple", "cat", "category", "bobcat"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(
+            first_match(&words, "cat", SearchMode::Prefix, false),
+            Some(1)
+        );
+        assert_eq!(first_match(&words, "dog", SearchMode::Prefix, false), None);
+        assert_eq!(
+            all_matches(&words, "cat", SearchMode::Contains, false),
+            [1, 2, 3]
+        );
+        assert_eq!(all_matches(&words, "cat", SearchMode::Contains, true), [1]);
+        assert_eq!(all_matches(&words, "cat", SearchMode::Exact, false), [1]);
+    }
+}
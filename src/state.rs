@@ -0,0 +1,48 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::{self, Value};
+use crate::search::SearchMode;
+
+/// Small bits of session state remembered between runs.
+#[derive(Default)]
+pub struct State {
+    /// Name of the dictionary that was open last.
+    pub dictionary: Option<String>,
+    pub search_mode: Option<SearchMode>,
+}
+
+impl State {
+    /// Loads the state file, ignoring it when missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        let mut state = Self::default();
+        let Ok(content) = fs::read_to_string(path) else {
+            return state;
+        };
+        let Ok(doc) = config::parse(&content) else {
+            return state;
+        };
+        let Some(table) = doc.get("") else {
+            return state;
+        };
+        if let Some(Value::String(name)) = table.get("dictionary") {
+            state.dictionary = Some(name.clone());
+        }
+        if let Some(Value::String(name)) = table.get("search_mode") {
+            state.search_mode = SearchMode::from_name(name);
+        }
+        state
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut content = String::new();
+        if let Some(name) = &self.dictionary {
+            content.push_str(&format!("dictionary = {}\n", config::quote(name)));
+        }
+        if let Some(mode) = self.search_mode {
+            content.push_str(&format!("search_mode = {}\n", config::quote(mode.name())));
+        }
+        fs::write(path, content)
+    }
+}
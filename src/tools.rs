@@ -0,0 +1,226 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::config::{Config, DictionaryConfig};
+use crate::schema::Schema;
+
+/// Resolves a dictionary given either as a file path or as a name in one of
+/// the dictionary folders, the first of which is used when none matches.
+pub fn resolve_dictionary(arg: &str, dirs: &[PathBuf], extensions: &[String]) -> PathBuf {
+    let path = PathBuf::from(arg);
+    if path.is_file() {
+        return path;
+    }
+    dirs.iter()
+        .flat_map(|dir| extensions.iter().map(|ext| dir.join([arg, ext].concat())))
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| dirs[0].join([arg, &extensions[0]].concat()))
+}
+
+fn open_read_only(path: &Path) -> Result<Connection, Box<dyn Error>> {
+    if !path.is_file() {
+        return Err(format!("'{}' does not exist", path.display()).into());
+    }
+    Ok(Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?)
+}
+
+fn load_headwords(
+    conn: &Connection,
+    config: &DictionaryConfig,
+) -> rusqlite::Result<BTreeSet<String>> {
+    let schema = Schema::detect(conn, config);
+    let mut stmt = conn.prepare(&schema.sql("SELECT {word} FROM dictionary"))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(row.get::<_, Option<String>>(0)?.unwrap_or_default())
+    })?;
+    rows.collect()
+}
+
+/// Compares the headwords of two dictionaries, printing the counts and either
+/// printing the lists or writing them into `output`.
+pub fn diff(
+    a: &Path,
+    b: &Path,
+    output: Option<&Path>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let name = |path: &Path| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let (name_a, name_b) = (name(a), name(b));
+    let words_a = load_headwords(&open_read_only(a)?, config.dictionary(&name_a))?;
+    let words_b = load_headwords(&open_read_only(b)?, config.dictionary(&name_b))?;
+    let sections = [
+        (
+            format!("only-in-{}", name_a),
+            words_a.difference(&words_b).collect::<Vec<_>>(),
+        ),
+        (
+            format!("only-in-{}", name_b),
+            words_b.difference(&words_a).collect(),
+        ),
+        (
+            "common".to_string(),
+            words_a.intersection(&words_b).collect(),
+        ),
+    ];
+    for (title, words) in &sections {
+        println!("{}: {}", title, words.len());
+    }
+    for (title, words) in &sections {
+        let list: String = words.iter().map(|w| format!("{}\n", w)).collect();
+        match output {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                let file = dir.join(format!("{}.txt", title));
+                fs::write(&file, list)?;
+                println!("Wrote {}", file.display());
+            }
+            None => print!("\n[{}]\n{}", title, list),
+        }
+    }
+    Ok(())
+}
+
+/// Problems found in a dictionary file.
+pub struct Report {
+    pub entries: usize,
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} entries checked.", self.entries)?;
+        if self.problems.is_empty() {
+            return writeln!(f, "No problems found.");
+        }
+        writeln!(f, "{} problem(s) found:", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a dictionary for a missing or malformed `dictionary` table, empty
+/// or duplicate headwords, and definitions containing control characters.
+/// Escape characters are accepted when the dictionary renders ANSI colors.
+pub fn validate(path: &Path, config: &DictionaryConfig) -> Result<Report, Box<dyn Error>> {
+    let conn = open_read_only(path)?;
+    let schema = Schema::detect(&conn, config);
+    let mut report = Report {
+        entries: 0,
+        problems: Vec::new(),
+    };
+    let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info('dictionary')")?;
+    let columns: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    if columns.is_empty() {
+        report
+            .problems
+            .push("missing table `dictionary`".to_string());
+        return Ok(report);
+    }
+    let mut schema_ok = true;
+    for expected in [&schema.word, &schema.definition] {
+        match columns
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(expected))
+        {
+            None => {
+                schema_ok = false;
+                report
+                    .problems
+                    .push(format!("missing column `{}`", expected));
+            }
+            Some((name, kind)) => {
+                let kind = kind.to_uppercase();
+                if !(kind.is_empty() || kind.contains("TEXT") || kind.contains("CHAR")) {
+                    report.problems.push(format!(
+                        "column `{}` has type {}, expected TEXT",
+                        name, kind
+                    ));
+                }
+            }
+        }
+    }
+    report.entries = conn.query_row("SELECT COUNT(*) FROM dictionary", [], |row| row.get(0))?;
+    if !schema_ok {
+        return Ok(report);
+    }
+
+    let count =
+        |sql: &str| -> rusqlite::Result<usize> { conn.query_row(sql, [], |row| row.get(0)) };
+    let empty_words = count(
+        &schema.sql("SELECT COUNT(*) FROM dictionary WHERE {word} IS NULL OR TRIM({word}) = ''"),
+    )?;
+    if empty_words > 0 {
+        report
+            .problems
+            .push(format!("{} entries with an empty word", empty_words));
+    }
+    let empty_definitions = count(&schema.sql(
+        "SELECT COUNT(*) FROM dictionary WHERE {definition} IS NULL OR TRIM({definition}) = ''",
+    ))?;
+    if empty_definitions > 0 {
+        report.problems.push(format!(
+            "{} entries with an empty definition",
+            empty_definitions
+        ));
+    }
+    let duplicates = count(&schema.sql(
+        "SELECT COUNT(*) FROM (SELECT {word} FROM dictionary GROUP BY {word} HAVING COUNT(*) > 1)",
+    ))?;
+    if duplicates > 0 {
+        report
+            .problems
+            .push(format!("{} headwords appear more than once", duplicates));
+    }
+
+    let mut stmt = conn.prepare(&schema.sql("SELECT {definition} FROM dictionary"))?;
+    let mut rows = stmt.query([])?;
+    let mut control = 0;
+    while let Some(row) = rows.next()? {
+        if let Ok(Some(definition)) = row.get::<_, Option<String>>(0) {
+            if definition.chars().any(|c| {
+                c.is_control() && !matches!(c, '\n' | '\r' | '\t') && !(config.ansi && c == '\x1b')
+            }) {
+                control += 1;
+            }
+        }
+    }
+    if control > 0 {
+        report.problems.push(format!(
+            "{} definitions contain stray control characters",
+            control
+        ));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_headwords_are_compared_as_empty() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dictionary (word TEXT, definition TEXT);
+             INSERT INTO dictionary VALUES (NULL, 'Orphan.'), ('blank', NULL);",
+        )
+        .unwrap();
+        let words = load_headwords(&conn, &DictionaryConfig::default()).unwrap();
+        assert_eq!(words.into_iter().collect::<Vec<_>>(), ["", "blank"]);
+    }
+}